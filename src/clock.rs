@@ -0,0 +1,147 @@
+use bevy::app::{FixedMain, RunFixedMainLoopSystem};
+use bevy::prelude::*;
+
+/// The slowest the simulation can run compared to real time
+const MIN_TIME_SCALE: f64 = 1.0 / 16.0;
+/// The fastest the simulation can run compared to real time
+const MAX_TIME_SCALE: f64 = 4.0;
+
+/// Ways to control the simulation clock. The simulation always moves in whole
+/// fixed steps, so these don't change the step size seen by the integrators.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockAction {
+    TogglePause,
+    /// Pause, then run a single fixed step
+    Step,
+    Slower,
+    Faster,
+}
+
+/// Fixed steps requested while the simulation is paused
+#[derive(Resource, Default)]
+struct PendingSteps(u32);
+
+/// Pause with space, step with period, and change the time scale with the
+/// square brackets
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClockAction>()
+            .init_resource::<PendingSteps>()
+            .add_systems(Update, (send_key_actions, apply_clock_actions).chain())
+            .add_systems(
+                RunFixedMainLoop,
+                run_pending_steps.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+            );
+    }
+}
+
+fn send_key_actions(keys: Res<ButtonInput<KeyCode>>, mut actions: EventWriter<ClockAction>) {
+    for (key, action) in [
+        (KeyCode::Space, ClockAction::TogglePause),
+        (KeyCode::Period, ClockAction::Step),
+        (KeyCode::BracketLeft, ClockAction::Slower),
+        (KeyCode::BracketRight, ClockAction::Faster),
+    ] {
+        if keys.just_pressed(key) {
+            actions.write(action);
+        }
+    }
+}
+
+fn apply_clock_actions(
+    mut actions: EventReader<ClockAction>,
+    mut time: ResMut<Time<Virtual>>,
+    mut pending_steps: ResMut<PendingSteps>,
+) {
+    for action in actions.read() {
+        match action {
+            ClockAction::TogglePause => {
+                if time.is_paused() {
+                    time.unpause();
+                } else {
+                    time.pause();
+                }
+            }
+            ClockAction::Step => {
+                time.pause();
+                pending_steps.0 += 1;
+            }
+            ClockAction::Slower => {
+                let speed = (time.relative_speed_f64() / 2.0).max(MIN_TIME_SCALE);
+                time.set_relative_speed_f64(speed);
+            }
+            ClockAction::Faster => {
+                let speed = (time.relative_speed_f64() * 2.0).min(MAX_TIME_SCALE);
+                time.set_relative_speed_f64(speed);
+            }
+        }
+    }
+}
+
+/// Paused virtual time doesn't advance the fixed clock, so steps are run by
+/// advancing the fixed clock by one time step ourselves, just like the fixed
+/// main loop does.
+fn run_pending_steps(world: &mut World) {
+    let steps = std::mem::take(&mut world.resource_mut::<PendingSteps>().0);
+    for _ in 0..steps {
+        let mut fixed_time = world.resource_mut::<Time<Fixed>>();
+        let timestep = fixed_time.timestep();
+        fixed_time.advance_by(timestep);
+
+        *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+        world.run_schedule(FixedMain);
+    }
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+
+    /// The time steps seen by the fixed systems
+    #[derive(Resource, Default)]
+    struct Steps(Vec<f64>);
+
+    fn record_step(time: Res<Time>, mut steps: ResMut<Steps>) {
+        steps.0.push(time.delta_secs_f64());
+    }
+
+    #[test]
+    fn test_pause_and_step() {
+        let timestep = Duration::from_secs_f64(1.0 / 60.0);
+
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, ClockPlugin))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<Steps>()
+            .insert_resource(Time::<Fixed>::from_duration(timestep))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+            .add_systems(FixedUpdate, record_step);
+
+        // the first update has no delta
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Steps>().0.len(), 3);
+
+        app.world_mut().send_event(ClockAction::TogglePause);
+        for _ in 0..4 {
+            app.update();
+        }
+        let paused_steps = app.world().resource::<Steps>().0.len();
+
+        app.world_mut().send_event(ClockAction::Step);
+        for _ in 0..4 {
+            app.update();
+        }
+        let steps = &app.world().resource::<Steps>().0;
+        assert_eq!(steps.len(), paused_steps + 1);
+        assert_eq!(*steps.last().unwrap(), timestep.as_secs_f64());
+    }
+}
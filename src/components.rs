@@ -2,6 +2,7 @@ use std::ops::Deref;
 
 use bevy::math::DVec2;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component, Clone, Copy)]
 #[require(SpringForce, Connection)]
@@ -42,6 +43,38 @@ impl From<Rotation> for f64 {
 #[derive(Component, Clone, Copy)]
 pub struct Tangible;
 
+/// Which tangible objects collide with each other. Two objects collide when
+/// each is a member of a layer in the filter of the other. Objects without
+/// this component are in every layer and collide with every layer.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionLayers {
+    /// Bit mask of the layers the object is in
+    pub memberships: u32,
+    /// Bit mask of the layers the object collides with
+    pub filter: u32,
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self {
+            memberships: u32::MAX,
+            filter: u32::MAX,
+        }
+    }
+}
+
+impl CollisionLayers {
+    pub const fn interacts_with(&self, other: &Self) -> bool {
+        self.memberships & other.filter != 0 && other.memberships & self.filter != 0
+    }
+}
+
+/// The two entities of the `Connection` on this entity don't collide with
+/// each other
+#[derive(Component, Clone, Copy)]
+#[require(Connection)]
+pub struct IgnoreCollisions;
+
 #[derive(Component, Clone, Copy)]
 pub struct Size {
     pub width: f64,
@@ -55,11 +88,36 @@ impl From<Size> for DVec2 {
 }
 
 #[derive(Component, Clone, Copy)]
-#[require(Position)]
+#[require(Position, Rotation)]
 pub struct PhysicsObject {
     pub velocity: DVec2,
     pub acceleration: DVec2,
     pub mass: f64,
+    pub angular_velocity: f64,
+    pub angular_acceleration: f64,
+    /// Moment of inertia around the center of mass. This is infinite until
+    /// it is calculated from the `Shape` and `Size` of the object, meaning
+    /// objects without a shape can't rotate.
+    pub moment_of_inertia: f64,
+    pub kind: BodyKind,
+}
+
+/// Mass per unit of area. Objects with a density get their mass from the
+/// area of their shape, so they stay consistent when they are resized.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Density(pub f64);
+
+/// How a physics object is allowed to move
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyKind {
+    /// Moved by forces, collisions and joints
+    #[default]
+    Dynamic,
+    /// Never moves. Other objects see it as having infinite mass.
+    Static,
+    /// Moves with its own velocity, which nothing but the scene changes.
+    /// Other objects see it as having infinite mass.
+    Kinematic,
 }
 
 impl PhysicsObject {
@@ -68,15 +126,171 @@ impl PhysicsObject {
             velocity: DVec2::ZERO,
             acceleration: DVec2::ZERO,
             mass,
+            angular_velocity: 0.0,
+            angular_acceleration: 0.0,
+            moment_of_inertia: f64::INFINITY,
+            kind: BodyKind::Dynamic,
+        }
+    }
+
+    /// Inverse of the mass felt when the object is pushed. Only dynamic
+    /// objects can be pushed, so it is zero for the others.
+    pub fn inverse_mass(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic => self.mass.recip(),
+            BodyKind::Static | BodyKind::Kinematic => 0.0,
+        }
+    }
+
+    /// Inverse of the moment of inertia felt when the object is turned. It is
+    /// zero for objects that can't be turned by others, and for objects
+    /// without a shape, whose moment of inertia is infinite.
+    pub fn inverse_moment_of_inertia(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic => self.moment_of_inertia.recip(),
+            BodyKind::Static | BodyKind::Kinematic => 0.0,
+        }
+    }
+
+    /// Velocity of a point offset from the center of mass
+    pub fn velocity_at(&self, offset: DVec2) -> DVec2 {
+        self.velocity + self.angular_velocity * offset.perp()
+    }
+
+    /// Apply an impulse at a point offset from the center of mass, changing
+    /// both the linear and the angular velocity.
+    pub fn apply_impulse_at(&mut self, impulse: DVec2, offset: DVec2) {
+        self.velocity += impulse * self.inverse_mass();
+        self.angular_velocity += offset.perp_dot(impulse) * self.inverse_moment_of_inertia();
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        let linear_energy = 0.5 * self.mass * self.velocity.length_squared();
+        if self.moment_of_inertia.is_finite() {
+            linear_energy + 0.5 * self.moment_of_inertia * self.angular_velocity.powi(2)
+        } else {
+            linear_energy
         }
     }
 }
 
-#[derive(Component, Clone, Copy)]
+/// Surface properties used when resolving collisions.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsMaterial {
+    /// How much of the normal velocity is kept after a bounce, in [0, 1]
+    pub restitution: f64,
+    /// Coulomb friction coefficient
+    pub friction: f64,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct SpringForce {
+    /// Damping of the relative velocity of the ends along the spring
     pub damping: f64,
     pub spring_constant: f64,
     pub equilibrium_length: f64,
+    #[serde(default)]
+    pub law: SpringLaw,
+    /// The spring breaks when its length differs from the equilibrium length
+    /// by more than this fraction of the equilibrium length
+    #[serde(default)]
+    pub max_strain: Option<f64>,
+}
+
+/// How the force of a spring depends on how far it is stretched
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpringLaw {
+    /// Hooke's law
+    #[default]
+    Linear,
+    /// Only pushes, like a spring that isn't attached at one end
+    CompressionOnly,
+    /// Only pulls, like a rope
+    TensionOnly,
+    /// A Duffing spring, which stiffens as it is stretched or compressed
+    /// when `coefficient` is positive
+    Cubic { coefficient: f64 },
+}
+
+impl SpringForce {
+    /// Whether the spring acts at all at this displacement from equilibrium
+    pub fn is_engaged(&self, displacement: f64) -> bool {
+        match self.law {
+            SpringLaw::Linear | SpringLaw::Cubic { .. } => true,
+            SpringLaw::CompressionOnly => displacement < 0.0,
+            SpringLaw::TensionOnly => displacement > 0.0,
+        }
+    }
+
+    /// The force pulling the ends together at this displacement from equilibrium
+    pub fn force(&self, displacement: f64) -> f64 {
+        if !self.is_engaged(displacement) {
+            return 0.0;
+        }
+        match self.law {
+            SpringLaw::Cubic { coefficient } => {
+                self.spring_constant * displacement + coefficient * displacement.powi(3)
+            }
+            _ => self.spring_constant * displacement,
+        }
+    }
+
+    /// The energy stored in the spring at this displacement from equilibrium
+    pub fn potential_energy(&self, displacement: f64) -> f64 {
+        if !self.is_engaged(displacement) {
+            return 0.0;
+        }
+        match self.law {
+            SpringLaw::Cubic { coefficient } => {
+                0.5 * self.spring_constant * displacement.powi(2)
+                    + 0.25 * coefficient * displacement.powi(4)
+            }
+            _ => 0.5 * self.spring_constant * displacement.powi(2),
+        }
+    }
+
+    pub fn is_broken(&self, displacement: f64) -> bool {
+        self.max_strain
+            .is_some_and(|max_strain| displacement.abs() > max_strain * self.equilibrium_length)
+    }
+}
+
+/// A rigid connection between the two entities of a `Connection`. It acts on
+/// anchor points, which are offsets from the entities' positions that rotate
+/// along with them.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[require(Connection)]
+pub struct Joint {
+    pub kind: JointKind,
+    pub anchor1: DVec2,
+    pub anchor2: DVec2,
+    /// Rotation of the second entity relative to the first that is kept by
+    /// joints that lock rotation
+    pub reference_angle: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    /// A massless rod keeping the anchors `length` apart
+    Distance { length: f64 },
+    /// A hinge pinning the anchors together
+    Revolute,
+    /// A slider keeping the second anchor on the line through the first one,
+    /// at `axis` radians in the first entity's frame. Rotation is locked.
+    Prismatic { axis: f64 },
+    /// Pins the anchors together and locks rotation
+    Weld,
+}
+
+impl Joint {
+    /// World positions of the anchors, given the positions and rotations of
+    /// the connected entities
+    pub fn anchor_positions(&self, body1: (DVec2, f64), body2: (DVec2, f64)) -> (DVec2, DVec2) {
+        (
+            body1.0 + DVec2::from_angle(body1.1).rotate(self.anchor1),
+            body2.0 + DVec2::from_angle(body2.1).rotate(self.anchor2),
+        )
+    }
 }
 
 #[derive(Component, Clone, Copy)]
@@ -94,12 +308,23 @@ impl Default for Size {
     }
 }
 
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.3,
+            friction: 0.4,
+        }
+    }
+}
+
 impl Default for SpringForce {
     fn default() -> Self {
         Self {
             damping: 0.0,
             spring_constant: 1.0,
             equilibrium_length: 1.0,
+            law: SpringLaw::Linear,
+            max_strain: None,
         }
     }
 }
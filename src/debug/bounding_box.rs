@@ -1,6 +1,6 @@
 use crate::WindowSize;
 use crate::components::{Position, Rotation, Size, Tangible};
-use crate::shapes::{Shape, ShapeImpl};
+use crate::shapes::{Compound, Shape, combined_bounding_box, placed_shapes};
 
 use bevy::prelude::*;
 
@@ -27,7 +27,11 @@ fn create_bounding_box(
     mut commands: Commands,
     query: Query<
         (Entity, &Position, &Size, &Rotation),
-        (With<Tangible>, With<Shape>, Without<BoundingBox>),
+        (
+            With<Tangible>,
+            Or<(With<Shape>, With<Compound>)>,
+            Without<BoundingBox>,
+        ),
     >,
 ) {
     for (entity, _position, _size, _rotation) in &query {
@@ -38,21 +42,35 @@ fn create_bounding_box(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn move_mounding_box(
     mut gizmos: Gizmos,
     mut commands: Commands,
     window: Res<WindowSize>,
-    shape_query: Query<(&Shape, &Position, &Size, &Rotation, &BoundingBoxColor), With<BoundingBox>>,
+    shape_query: Query<
+        (
+            Option<&Shape>,
+            Option<&Compound>,
+            &Position,
+            &Size,
+            &Rotation,
+            &BoundingBoxColor,
+        ),
+        With<BoundingBox>,
+    >,
     mut pointer_query: Query<(Entity, &EntityPointer), Without<BoundingBox>>,
 ) {
     for (entity, entity_pointer) in &mut pointer_query {
-        let Ok((shape, position, size, rotation, color)) = shape_query.get(entity_pointer.0) else {
+        let Ok((shape, compound, position, size, rotation, color)) =
+            shape_query.get(entity_pointer.0)
+        else {
             commands.entity(entity).despawn();
             continue;
         };
-        let data = (*position, *size, *rotation).into();
-
-        let bounding_box = shape.get_bounding_box(&data);
+        let shapes = placed_shapes(shape, compound, (*position, *size, *rotation).into());
+        let Some(bounding_box) = combined_bounding_box(&shapes) else {
+            continue;
+        };
         gizmos.rect_2d(
             bounding_box.center().as_vec2() * window.scale,
             bounding_box.size().as_vec2() * window.scale,
@@ -1,9 +1,14 @@
 use bevy::diagnostic::{
     Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
 };
+use std::time::Duration;
+
 use bevy::prelude::*;
 
 use crate::Energy;
+use crate::clock::ClockAction;
+use crate::physics::{DissipatedEnergy, Integrators, SubSteps, WorldBoundary};
+use crate::replay::{InputQueue, SimulationInput};
 
 #[derive(Component)]
 struct DebugRoot;
@@ -14,6 +19,39 @@ struct FpsText;
 #[derive(Component)]
 struct EnergyText;
 
+#[derive(Component)]
+struct DissipatedEnergyText;
+
+#[derive(Component)]
+struct IntegratorText;
+
+#[derive(Component)]
+struct TickRateText;
+
+#[derive(Component)]
+struct SubStepsText;
+
+#[derive(Component)]
+struct BoundaryText;
+
+/// Text that switches a simulation setting to its next value when clicked
+#[derive(Component, Clone, Copy)]
+enum SettingButton {
+    Integrator,
+    TickRate,
+    SubSteps,
+    Boundary,
+}
+
+/// The tick rates the tick rate button cycles through, in Hz
+const TICK_RATES: [f64; 4] = [30.0, 60.0, 120.0, 240.0];
+
+#[derive(Component)]
+struct TimeScaleText;
+
+#[derive(Component)]
+struct ClockButton(ClockAction);
+
 pub struct DebugInfoPlugin;
 
 impl Plugin for DebugInfoPlugin {
@@ -23,7 +61,20 @@ impl Plugin for DebugInfoPlugin {
         )
         .add_systems(Startup, setup_fps_counter)
         .add_systems(Update, FrameTimeDiagnosticsPlugin::diagnostic_system)
-        .add_systems(Update, (update_fps_text, update_energy_text));
+        .add_systems(
+            Update,
+            (
+                update_fps_text,
+                update_energy_text,
+                update_dissipated_energy_text,
+                update_integrator_text.run_if(resource_changed::<Integrators>),
+                update_step_text,
+                update_boundary_text.run_if(resource_changed::<WorldBoundary>),
+                setting_button_system,
+                update_time_scale_text,
+                clock_button_system,
+            ),
+        );
     }
 }
 
@@ -70,10 +121,65 @@ fn setup_fps_counter(mut commands: Commands) {
 
     let fps_text = spawn_debug_text(&mut commands, FpsText, "FPS: ");
     let initial_energy_text = spawn_debug_text(&mut commands, EnergyText, "  E: ");
-
+    let dissipated_energy_text = spawn_debug_text(&mut commands, DissipatedEnergyText, "  D: ");
+    // clicking the integrator and step texts switches to the next setting
+    let integrator_text = spawn_debug_text(&mut commands, IntegratorText, "  I: ");
+    commands
+        .entity(integrator_text)
+        .insert((Button, SettingButton::Integrator));
+    let tick_rate_text = spawn_debug_text(&mut commands, TickRateText, "  R: ");
+    commands
+        .entity(tick_rate_text)
+        .insert((Button, SettingButton::TickRate));
+    let sub_steps_text = spawn_debug_text(&mut commands, SubStepsText, "  S: ");
     commands
-        .entity(root)
-        .add_children(&[fps_text, initial_energy_text]);
+        .entity(sub_steps_text)
+        .insert((Button, SettingButton::SubSteps));
+    let boundary_text = spawn_debug_text(&mut commands, BoundaryText, "  B: ");
+    commands
+        .entity(boundary_text)
+        .insert((Button, SettingButton::Boundary));
+
+    let time_scale_text = spawn_debug_text(&mut commands, TimeScaleText, "  T: ");
+
+    let clock_buttons = commands
+        .spawn(Node {
+            column_gap: Val::Px(8.0),
+            ..Default::default()
+        })
+        .id();
+    for (action, label) in [
+        (ClockAction::TogglePause, "[pause]"),
+        (ClockAction::Step, "[step]"),
+        (ClockAction::Slower, "[-]"),
+        (ClockAction::Faster, "[+]"),
+    ] {
+        let button = commands
+            .spawn((
+                Button,
+                ClockButton(action),
+                Text::new(label),
+                TextFont {
+                    font_size: 16.0,
+                    ..Default::default()
+                },
+                TextColor::from(Color::WHITE),
+            ))
+            .id();
+        commands.entity(clock_buttons).add_child(button);
+    }
+
+    commands.entity(root).add_children(&[
+        fps_text,
+        initial_energy_text,
+        dissipated_energy_text,
+        integrator_text,
+        tick_rate_text,
+        sub_steps_text,
+        boundary_text,
+        time_scale_text,
+        clock_buttons,
+    ]);
 }
 
 fn update_fps_text(
@@ -98,3 +204,139 @@ fn update_energy_text(energy: Res<Energy>, mut query: Query<&mut TextSpan, With<
         text.0 = format!("{:.3}", energy.0);
     }
 }
+
+fn update_dissipated_energy_text(
+    dissipated_energy: Res<DissipatedEnergy>,
+    mut query: Query<&mut TextSpan, With<DissipatedEnergyText>>,
+) {
+    for mut text in &mut query {
+        text.0 = format!("{:.3}", dissipated_energy.total);
+    }
+}
+
+fn update_integrator_text(
+    integrator: Res<Integrators>,
+    mut query: Query<&mut TextSpan, With<IntegratorText>>,
+) {
+    for mut text in &mut query {
+        text.0 = integrator.to_string();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_step_text(
+    fixed_time: Res<Time<Fixed>>,
+    sub_steps: Res<SubSteps>,
+    mut tick_rate_query: Query<&mut TextSpan, (With<TickRateText>, Without<SubStepsText>)>,
+    mut sub_steps_query: Query<&mut TextSpan, (With<SubStepsText>, Without<TickRateText>)>,
+) {
+    for mut text in &mut tick_rate_query {
+        text.0 = format!("{:.0} Hz", 1.0 / fixed_time.timestep().as_secs_f64());
+    }
+    for mut text in &mut sub_steps_query {
+        text.0 = format!("{} sub-steps", sub_steps.0);
+    }
+}
+
+fn update_boundary_text(
+    boundary: Res<WorldBoundary>,
+    mut query: Query<&mut TextSpan, With<BoundaryText>>,
+) {
+    for mut text in &mut query {
+        text.0 = boundary.kind.to_string();
+    }
+}
+
+/// The next setting for a button. Only combinations with small enough
+/// sub-steps are picked.
+fn next_setting(
+    button: SettingButton,
+    integrator: Integrators,
+    timestep: Duration,
+    sub_steps: SubSteps,
+    boundary: WorldBoundary,
+) -> SimulationInput {
+    match button {
+        SettingButton::Integrator => SimulationInput::SetIntegrator(integrator.next()),
+        SettingButton::TickRate => {
+            let tick_rate = 1.0 / timestep.as_secs_f64();
+            let next = TICK_RATES
+                .into_iter()
+                .find(|rate| *rate > tick_rate + 0.5)
+                .unwrap_or(TICK_RATES[0]);
+            SimulationInput::SetTimestep(Duration::from_secs_f64(1.0 / next))
+        }
+        SettingButton::SubSteps => {
+            let mut next = sub_steps.next();
+            while !next.is_stable(timestep) {
+                next = next.next();
+            }
+            SimulationInput::SetSubSteps(next)
+        }
+        SettingButton::Boundary => SimulationInput::SetBoundary(WorldBoundary {
+            kind: boundary.kind.next(),
+            ..boundary
+        }),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn setting_button_system(
+    mut query: Query<(&Interaction, &SettingButton, &mut TextColor), Changed<Interaction>>,
+    integrator: Res<Integrators>,
+    fixed_time: Res<Time<Fixed>>,
+    sub_steps: Res<SubSteps>,
+    boundary: Res<WorldBoundary>,
+    mut queue: ResMut<InputQueue>,
+) {
+    for (interaction, button, mut color) in &mut query {
+        match interaction {
+            Interaction::Pressed => {
+                queue.push(next_setting(
+                    *button,
+                    *integrator,
+                    fixed_time.timestep(),
+                    *sub_steps,
+                    *boundary,
+                ));
+            }
+            Interaction::Hovered => {
+                *color = Color::srgb_u8(150, 150, 150).into();
+            }
+            Interaction::None => {
+                *color = Color::WHITE.into();
+            }
+        }
+    }
+}
+
+fn update_time_scale_text(
+    time: Res<Time<Virtual>>,
+    mut query: Query<&mut TextSpan, With<TimeScaleText>>,
+) {
+    for mut text in &mut query {
+        text.0 = format!("{}x", time.relative_speed());
+        if time.is_paused() {
+            text.0 += " (paused)";
+        }
+    }
+}
+
+fn clock_button_system(
+    mut query: Query<(&Interaction, &ClockButton, &mut TextColor), Changed<Interaction>>,
+    mut actions: EventWriter<ClockAction>,
+) {
+    for (interaction, button, mut color) in &mut query {
+        match interaction {
+            Interaction::Pressed => {
+                actions.write(button.0);
+            }
+            Interaction::Hovered => {
+                *color = Color::srgb_u8(150, 150, 150).into();
+            }
+            Interaction::None => {
+                *color = Color::WHITE.into();
+            }
+        }
+    }
+}
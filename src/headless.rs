@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::{Level, LogPlugin};
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::scenes::{GameScene, SimulationScenePlugin};
+
+/// How many fixed steps are left before the app exits
+#[derive(Resource)]
+struct RemainingSteps(u64);
+
+/// Run a scene without a window or renderer for a fixed number of steps.
+/// Every update advances the simulation by exactly one fixed time step,
+/// so the simulation runs as fast as possible and gives the same result
+/// regardless of how fast the computer is.
+pub struct HeadlessPlugin {
+    pub scene: GameScene,
+    pub steps: u64,
+    pub timestep: Duration,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            LogPlugin {
+                filter: "physics_engine=debug".into(),
+                level: Level::WARN,
+                custom_layer: |_| None,
+            },
+            StatesPlugin,
+            AssetPlugin::default(),
+        ))
+        // the scenes still create meshes and materials, they are just never drawn
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(self.timestep))
+        .insert_resource(RemainingSteps(self.steps))
+        .insert_state(self.scene)
+        .add_plugins(SimulationScenePlugin)
+        .add_systems(FixedLast, count_steps)
+        .add_systems(Last, follow_timestep);
+    }
+}
+
+/// Keep each update one fixed step long when the time step is changed, like
+/// when a snapshot or replay sets its own
+fn follow_timestep(fixed_time: Res<Time<Fixed>>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let timestep = fixed_time.timestep();
+    if !matches!(*strategy, TimeUpdateStrategy::ManualDuration(duration) if duration == timestep) {
+        *strategy = TimeUpdateStrategy::ManualDuration(timestep);
+    }
+}
+
+fn count_steps(mut remaining: ResMut<RemainingSteps>, mut exit: EventWriter<AppExit>) {
+    remaining.0 = remaining.0.saturating_sub(1);
+    if remaining.0 == 0 {
+        exit.write(AppExit::Success);
+    }
+}
@@ -1,25 +1,40 @@
+mod clock;
 mod components;
 mod debug;
+mod headless;
 mod mouse;
 mod physics;
+mod replay;
 mod scenes;
 mod shapes;
+mod snapshot;
 mod spawners;
 mod utils;
 
+use clock::ClockPlugin;
 use debug::bounding_box::ShowBoundingBoxPlugin;
 use debug::menu::DebugInfoPlugin;
+use headless::HeadlessPlugin;
 use mouse::InteractivityPlugin;
-use physics::PhysicsPlugin;
-use scenes::{GameScene, ScenePlugin};
+use physics::{
+    BoundaryKind, Integrators, PhysicsPlugin, SubSteps, WorldBoundary, update_transform,
+};
+use replay::{InputQueue, Recording, Replay, ReplayPlugin, Replaying, SimulationInput};
+use scenes::{GameScene, SceneDescription, SceneFile, ScenePlugin};
+use shapes::{update_compound_meshes, update_rounded_meshes};
+use snapshot::{LoadedSnapshot, Snapshot, SnapshotFile, SnapshotKeysPlugin, SnapshotPlugin};
 
 use std::ffi::OsString;
 use std::fs::File;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use bevy::log::{Level, LogPlugin};
+use bevy::math::DVec2;
 use bevy::prelude::*;
 use bevy::window::{MonitorSelection, PrimaryWindow, WindowPosition, WindowResolution};
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser};
 
 #[derive(Resource, Default)]
 struct Energy(f64);
@@ -27,12 +42,23 @@ struct Energy(f64);
 #[derive(Resource, Default)]
 struct EnergyFile(Option<OsString>);
 
+#[derive(Resource, Default)]
+struct StateFile(Option<OsString>);
+
 #[derive(Resource, Default)]
 struct WindowSize {
     size: Vec2,
     scale: f32,
 }
 
+impl WindowSize {
+    /// Half the width and height of the area the camera shows, in world
+    /// units. The camera is zoomed out so the shorter side is 4 units long.
+    fn visible_half_size(&self) -> Vec2 {
+        2.0 * self.size / self.scale
+    }
+}
+
 #[derive(Resource, Default)]
 struct MousePosition(Vec2);
 
@@ -57,6 +83,33 @@ fn update_window_size(camera_query: Query<&Camera>, mut window: ResMut<WindowSiz
     window.size = viewport_size;
 }
 
+/// Keep the world boundary on the edges of the screen. The boundary changes
+/// the simulation, so resizing goes through the input queue.
+fn fit_boundary_to_window(
+    window: Res<WindowSize>,
+    boundary: Res<WorldBoundary>,
+    mut queue: ResMut<InputQueue>,
+    mut requested: Local<Option<WorldBoundary>>,
+) {
+    if boundary.kind == BoundaryKind::None || window.scale == 0.0 {
+        return;
+    }
+
+    let half_size = window.visible_half_size().as_dvec2();
+    let difference = half_size - DVec2::from(boundary.half_size);
+    if difference.abs().max_element() < 1e-4 {
+        return;
+    }
+    let fitted = WorldBoundary {
+        half_size: half_size.to_array(),
+        ..*boundary
+    };
+    if *requested != Some(fitted) {
+        *requested = Some(fitted);
+        queue.push(SimulationInput::SetBoundary(fitted));
+    }
+}
+
 fn update_mouse_position(
     window_query: Query<&Window, With<PrimaryWindow>>,
     window: Res<WindowSize>,
@@ -79,56 +132,247 @@ fn update_mouse_position(
 }
 
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("length").args(["steps", "duration"]).requires("headless")))]
 struct Args {
     /// File to save energy data to. If not set, no data is saved
     #[arg(short, long)]
     energy_file: Option<OsString>,
-}
 
-fn main() {
-    let args = Args::parse();
+    /// File to save the state of every physics object to after each fixed
+    /// step, stamped with the simulated time. If not set, no data is saved
+    #[arg(short, long)]
+    state_file: Option<OsString>,
 
-    if let Some(file) = &args.energy_file {
-        if let Err(err) = File::create(file) {
-            panic!("Failed to create energy file: {err}");
-        }
+    /// Scene to start in
+    #[arg(long, value_enum, default_value_t)]
+    scene: GameScene,
+
+    /// RON or JSON file describing a scene to start in, instead of a built-in scene
+    #[arg(short = 'f', long, conflicts_with = "scene")]
+    scene_file: Option<PathBuf>,
+
+    /// Snapshot to start from, instead of a built-in scene
+    #[arg(short, long, conflicts_with_all = ["scene", "scene_file"])]
+    load_snapshot: Option<PathBuf>,
+
+    /// File to save snapshots to when the app exits. Snapshots can also be
+    /// saved with F5 and loaded with F9, which uses `snapshot.ron` if this is not set
+    #[arg(long)]
+    save_snapshot: Option<PathBuf>,
+
+    /// Record the initial state and every input to a replay file when the app exits
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Replay file to play back, instead of a built-in scene
+    #[arg(long, conflicts_with_all = ["scene", "scene_file", "load_snapshot"])]
+    replay: Option<PathBuf>,
+
+    /// Run the scene without a window, then exit. Requires either
+    /// `--steps`, `--duration` or `--replay`
+    #[arg(long)]
+    headless: bool,
+
+    /// Number of physics steps to simulate when running headless
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    steps: Option<u64>,
+
+    /// Number of seconds to simulate when running headless
+    #[arg(long)]
+    duration: Option<f64>,
+
+    /// Number of fixed steps per simulated second. Can be changed at runtime
+    #[arg(long, default_value_t = 60.0)]
+    tick_rate: f64,
+
+    /// Number of physics steps each fixed step is split into. More sub-steps
+    /// keep stiff springs stable. Can be changed at runtime
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=SubSteps::MAX as i64))]
+    substeps: u32,
+
+    /// Edges of the world that objects collide with or wrap around. They
+    /// follow the edges of the window. Can be changed at runtime
+    #[arg(long, value_enum, default_value_t)]
+    boundary: BoundaryKind,
+
+    /// Integrator used to step the simulation. Can be changed at runtime
+    #[arg(short, long, value_enum, default_value_t)]
+    integrator: Integrators,
+}
+
+fn create_output_file(file: &Option<OsString>, name: &str) {
+    if let Some(file) = file
+        && let Err(err) = File::create(file)
+    {
+        panic!("Failed to create {name} file: {err}");
     }
+}
 
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        // mode: WindowMode::Fullscreen,
-                        canvas: Some("#gameCanvas".into()),
-                        resolution: WindowResolution::new(960.0, 540.0),
-                        position: WindowPosition::Centered(MonitorSelection::Primary),
-                        title: "Physics engine".to_owned(),
-                        ..Default::default()
-                    }),
+fn add_windowed_plugins(app: &mut App, scene: GameScene) {
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    // mode: WindowMode::Fullscreen,
+                    canvas: Some("#gameCanvas".into()),
+                    resolution: WindowResolution::new(960.0, 540.0),
+                    position: WindowPosition::Centered(MonitorSelection::Primary),
+                    title: "Physics engine".to_owned(),
                     ..Default::default()
-                })
-                .set(LogPlugin {
-                    filter: "physics_engine=debug".into(),
-                    level: Level::WARN,
-                    custom_layer: |_| None,
                 }),
+                ..Default::default()
+            })
+            .set(LogPlugin {
+                filter: "physics_engine=debug".into(),
+                level: Level::WARN,
+                custom_layer: |_| None,
+            }),
+    )
+    .insert_resource(ClearColor(Color::WHITE))
+    .insert_resource(WindowSize::default())
+    .insert_resource(MousePosition::default())
+    .insert_state(scene)
+    .add_plugins((
+        DebugInfoPlugin,
+        ScenePlugin,
+        InteractivityPlugin,
+        ShowBoundingBoxPlugin,
+        SnapshotKeysPlugin,
+        ClockPlugin,
+    ))
+    .add_systems(Startup, add_camera)
+    .add_systems(
+        PreUpdate,
+        (
+            update_window_size,
+            (update_mouse_position, fit_boundary_to_window),
         )
-        .insert_resource(ClearColor(Color::WHITE))
-        .insert_resource(Energy::default())
-        .insert_resource(WindowSize::default())
-        .insert_resource(MousePosition::default())
-        .insert_resource(Time::<Fixed>::from_hz(60.0))
+            .chain(),
+    )
+    .add_systems(
+        Update,
+        (
+            update_transform,
+            update_rounded_meshes,
+            update_compound_meshes,
+        ),
+    );
+}
+
+fn main() {
+    let mut args = Args::parse();
+
+    let scene_file = args.scene_file.as_deref().map(|path| {
+        SceneDescription::load(path)
+            .unwrap_or_else(|err| Args::command().error(ErrorKind::InvalidValue, err).exit())
+    });
+    if scene_file.is_some() {
+        args.scene = GameScene::File;
+    }
+
+    let mut snapshot = args.load_snapshot.as_deref().map(|path| {
+        Snapshot::load(path)
+            .unwrap_or_else(|err| Args::command().error(ErrorKind::InvalidValue, err).exit())
+    });
+
+    let replay = args.replay.as_deref().map(|path| {
+        Replay::load(path)
+            .unwrap_or_else(|err| Args::command().error(ErrorKind::InvalidValue, err).exit())
+    });
+    if let Some(replay) = &replay {
+        snapshot = Some(replay.initial.clone());
+    }
+
+    if snapshot.is_some() {
+        args.scene = GameScene::Snapshot;
+    }
+
+    create_output_file(&args.energy_file, "energy");
+    create_output_file(&args.state_file, "state");
+
+    if !(args.tick_rate.is_finite() && args.tick_rate > 0.0) {
+        Args::command()
+            .error(ErrorKind::InvalidValue, "--tick-rate must be positive")
+            .exit();
+    }
+    let timestep = Duration::from_secs_f64(1.0 / args.tick_rate);
+    let sub_steps = SubSteps(args.substeps);
+    if !sub_steps.is_stable(timestep) {
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "a tick rate of {} Hz needs at least {} sub-steps",
+                    args.tick_rate,
+                    SubSteps::minimum(timestep).0
+                ),
+            )
+            .exit();
+    }
+
+    let mut app = App::new();
+    if args.headless {
+        if !args.scene.is_simulation() {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("the {} scene can't be run headless", args.scene),
+                )
+                .exit();
+        }
+
+        let steps = if let Some(steps) = args.steps {
+            steps
+        } else if let Some(duration) = args.duration {
+            (duration * args.tick_rate).round().max(1.0) as u64
+        } else if let Some(replay) = &replay {
+            replay.ticks.max(1)
+        } else {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--headless requires --steps, --duration or --replay",
+                )
+                .exit();
+        };
+        app.add_plugins(HeadlessPlugin {
+            scene: args.scene,
+            steps,
+            timestep,
+        });
+    } else {
+        add_windowed_plugins(&mut app, args.scene);
+    }
+
+    if let Some(description) = scene_file {
+        app.insert_resource(SceneFile(description));
+    }
+    if let Some(snapshot) = snapshot {
+        app.insert_resource(LoadedSnapshot(snapshot));
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(Replaying::new(replay));
+    }
+    if let Some(path) = args.record {
+        app.insert_resource(Recording::new(path));
+    }
+
+    app.insert_resource(Energy::default())
+        .insert_resource(Time::<Fixed>::from_duration(timestep))
         .insert_resource(EnergyFile(args.energy_file))
-        .init_state::<GameScene>()
-        .add_plugins((
-            PhysicsPlugin,
-            DebugInfoPlugin,
-            ScenePlugin,
-            InteractivityPlugin,
-            ShowBoundingBoxPlugin,
-        ))
-        .add_systems(Startup, add_camera)
-        .add_systems(PreUpdate, (update_window_size, update_mouse_position))
+        .insert_resource(StateFile(args.state_file))
+        .insert_resource(sub_steps)
+        .insert_resource(args.integrator)
+        .insert_resource(WorldBoundary {
+            kind: args.boundary,
+            ..Default::default()
+        })
+        .insert_resource(SnapshotFile {
+            save_on_exit: args.save_snapshot.is_some(),
+            path: args
+                .save_snapshot
+                .unwrap_or_else(|| PathBuf::from("snapshot.ron")),
+        })
+        .add_plugins((PhysicsPlugin, SnapshotPlugin, ReplayPlugin))
         .run();
 }
@@ -1,5 +1,6 @@
 use crate::components::{PhysicsObject, Position, Rotation, Size, Spring, Tangible};
-use crate::shapes::{Shape, ShapeImpl, SpringShape};
+use crate::replay::{InputQueue, SimulationInput};
+use crate::shapes::{Compound, Shape, ShapeImpl, SpringShape, placed_shapes};
 use crate::spawners::{Spawner, spring::spring_bundle};
 use crate::{MousePosition, WindowSize};
 
@@ -20,53 +21,91 @@ impl Plugin for InteractivityPlugin {
                 Update,
                 (
                     highlight_hovered_entity,
-                    create_mouse_spring.run_if(input_just_pressed(MouseButton::Left)),
-                    move_mouse_spring.run_if(input_pressed(MouseButton::Left)),
-                    destroy_mouse_spring.run_if(input_just_released(MouseButton::Left)),
+                    queue_mouse_input(SimulationInput::GrabWithMouse)
+                        .run_if(input_just_pressed(MouseButton::Left)),
+                    queue_mouse_input(SimulationInput::MoveMouse)
+                        .run_if(input_pressed(MouseButton::Left)),
+                    queue_release_mouse.run_if(input_just_released(MouseButton::Left)),
                 ),
             );
     }
 }
 
 #[derive(Component)]
-struct MouseEntity;
+pub struct MouseEntity;
 
+/// Outline the body under the mouse. Every shape of a compound is outlined,
+/// whichever one is hovered.
 #[allow(clippy::type_complexity)]
 fn highlight_hovered_entity(
     mut gizmos: Gizmos<HighlightGizmos>,
     window: Res<WindowSize>,
     mouse_position_resource: Res<MousePosition>,
     entity_query: Query<
-        (&Shape, &Position, &Size, &Rotation),
+        (
+            Option<&Shape>,
+            Option<&Compound>,
+            &Position,
+            &Size,
+            &Rotation,
+        ),
         (With<Tangible>, With<PhysicsObject>),
     >,
 ) {
     let mouse_position = mouse_position_resource.0.as_dvec2();
 
-    for (shape, position, size, rotation) in &entity_query {
-        let data = (*position, *size, *rotation).into();
-        if !shape.collides_with_point(&data, mouse_position) {
+    for (shape, compound, position, size, rotation) in &entity_query {
+        let shapes = placed_shapes(shape, compound, (*position, *size, *rotation).into());
+        if !shapes
+            .iter()
+            .any(|(shape, data)| shape.collides_with_point(data, mouse_position))
+        {
             continue;
         }
 
-        let vertices = shape.get_shape_vertices(&data);
-        let points = vertices
-            .iter()
-            .map(|vertex| vertex * window.scale)
-            .chain([vertices[0] * window.scale]);
-        gizmos.linestrip_2d(points, Color::srgb_u8(50, 200, 50));
+        for (shape, data) in &shapes {
+            let vertices = shape.get_shape_vertices(data);
+            let points = vertices
+                .iter()
+                .map(|vertex| vertex * window.scale)
+                .chain([vertices[0] * window.scale]);
+            gizmos.linestrip_2d(points, Color::srgb_u8(50, 200, 50));
+        }
     }
 }
 
-fn create_mouse_spring(
-    mouse_position_resource: Res<MousePosition>,
-    entity_query: Query<(Entity, &Shape, &Position, &Size, &Rotation), With<PhysicsObject>>,
+/// Mouse input changes the simulation, so it goes through the input queue
+fn queue_mouse_input(
+    input: fn([f64; 2]) -> SimulationInput,
+) -> impl FnMut(Res<MousePosition>, ResMut<InputQueue>) {
+    move |mouse_position, mut queue| {
+        queue.push(input(mouse_position.0.as_dvec2().to_array()));
+    }
+}
+
+fn queue_release_mouse(mut queue: ResMut<InputQueue>) {
+    queue.push(SimulationInput::ReleaseMouse);
+}
+
+/// Attach a spring between the mouse and the physics object under it
+#[allow(clippy::type_complexity)]
+pub fn create_mouse_spring(
+    In(mouse_position): In<DVec2>,
+    entity_query: Query<
+        (
+            Entity,
+            Option<&Shape>,
+            Option<&Compound>,
+            &Position,
+            &Size,
+            &Rotation,
+        ),
+        With<PhysicsObject>,
+    >,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let mouse_position = mouse_position_resource.0.as_dvec2();
-
     let Some((clicked_entity, entity_position)) = get_clicked_entity(mouse_position, entity_query)
     else {
         return;
@@ -99,16 +138,16 @@ fn create_mouse_spring(
         .id();
 }
 
-fn move_mouse_spring(
-    mouse_position: Res<MousePosition>,
+pub fn move_mouse_spring(
+    In(mouse_position): In<DVec2>,
     mut mouse_entity_query: Query<&mut Position, (With<MouseEntity>, Without<Spring>)>,
 ) {
     if let Ok(mut mouse_entity_pos) = mouse_entity_query.single_mut() {
-        mouse_entity_pos.0 = mouse_position.0.as_dvec2();
+        mouse_entity_pos.0 = mouse_position;
     }
 }
 
-fn destroy_mouse_spring(
+pub fn destroy_mouse_spring(
     mouse_entity_query: Query<Entity, With<MouseEntity>>,
     mut commands: Commands,
 ) {
@@ -119,10 +158,23 @@ fn destroy_mouse_spring(
 
 pub fn get_clicked_entity<'a>(
     mouse_position: DVec2,
-    entity_query: impl IntoIterator<Item = (Entity, &'a Shape, &'a Position, &'a Size, &'a Rotation)>,
+    entity_query: impl IntoIterator<
+        Item = (
+            Entity,
+            Option<&'a Shape>,
+            Option<&'a Compound>,
+            &'a Position,
+            &'a Size,
+            &'a Rotation,
+        ),
+    >,
 ) -> Option<(Entity, DVec2)> {
-    for (entity, shape, position, size, rotation) in entity_query {
-        if shape.collides_with_point(&(*position, *size, *rotation).into(), mouse_position) {
+    for (entity, shape, compound, position, size, rotation) in entity_query {
+        let shapes = placed_shapes(shape, compound, (*position, *size, *rotation).into());
+        if shapes
+            .iter()
+            .any(|(shape, data)| shape.collides_with_point(data, mouse_position))
+        {
             return Some((entity, **position));
         }
     }
@@ -0,0 +1,236 @@
+use std::fmt;
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::components::{PhysicsObject, Position, Size, Tangible};
+use crate::shapes::Shape;
+use crate::spawners::Spawner;
+
+/// How thick the walls are. They only need to be thick enough that fast
+/// objects can't pass through them in a single step.
+const WALL_THICKNESS: f64 = 1.0;
+
+/// Which edges of the world objects can't leave
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, EnumIter, Serialize, Deserialize,
+)]
+pub enum BoundaryKind {
+    /// Objects can go anywhere
+    #[default]
+    None,
+    Floor,
+    /// Walls on the left and right
+    Walls,
+    /// A floor, a ceiling and walls on both sides
+    Box,
+    /// Objects leaving on one side come back in on the other
+    Wrap,
+}
+
+impl fmt::Display for BoundaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Floor => f.write_str("floor"),
+            Self::Walls => f.write_str("walls"),
+            Self::Box => f.write_str("box"),
+            Self::Wrap => f.write_str("wrap"),
+        }
+    }
+}
+
+impl BoundaryKind {
+    /// The boundary after this one, wrapping around at the end
+    pub fn next(self) -> Self {
+        Self::iter()
+            .cycle()
+            .skip_while(|kind| *kind != self)
+            .nth(1)
+            .expect("there should be more than one boundary")
+    }
+}
+
+/// The edges of the world, around an area centered on the origin
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WorldBoundary {
+    pub kind: BoundaryKind,
+    /// Half the width and height of the area
+    pub half_size: [f64; 2],
+}
+
+impl Default for WorldBoundary {
+    /// The area the camera shows in the default 16:9 window
+    fn default() -> Self {
+        Self {
+            kind: BoundaryKind::None,
+            half_size: [32.0 / 9.0, 2.0],
+        }
+    }
+}
+
+impl WorldBoundary {
+    /// The center and size of each wall
+    fn walls(&self) -> Vec<(DVec2, DVec2)> {
+        let half_size = DVec2::from(self.half_size);
+        let offset = half_size + WALL_THICKNESS / 2.0;
+        // the floor and ceiling reach past the corners so there are no gaps
+        let horizontal = DVec2::new(2.0 * (half_size.x + WALL_THICKNESS), WALL_THICKNESS);
+        let vertical = DVec2::new(WALL_THICKNESS, 2.0 * half_size.y);
+
+        let floor = (DVec2::new(0.0, -offset.y), horizontal);
+        let ceiling = (DVec2::new(0.0, offset.y), horizontal);
+        let left = (DVec2::new(-offset.x, 0.0), vertical);
+        let right = (DVec2::new(offset.x, 0.0), vertical);
+        match self.kind {
+            BoundaryKind::None | BoundaryKind::Wrap => Vec::new(),
+            BoundaryKind::Floor => vec![floor],
+            BoundaryKind::Walls => vec![left, right],
+            BoundaryKind::Box => vec![floor, ceiling, left, right],
+        }
+    }
+}
+
+/// A wall of the world boundary
+#[derive(Component, Clone, Copy)]
+pub struct BoundaryWall;
+
+/// Replace the walls whenever the boundary changes. The walls are ordinary
+/// tangible shapes without a `PhysicsObject`, so collisions treat them like
+/// any other immovable object.
+pub fn update_boundary_walls(
+    boundary: Res<WorldBoundary>,
+    wall_query: Query<Entity, With<BoundaryWall>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for entity in &wall_query {
+        commands.entity(entity).despawn();
+    }
+
+    for (position, size) in boundary.walls() {
+        Spawner::new(BoundaryWall, &mut commands)
+            .with_bundle((
+                Position(position),
+                Size {
+                    width: size.x,
+                    height: size.y,
+                },
+                Tangible,
+            ))
+            .with_shape(Shape::Square, &mut meshes)
+            .with_color(Color::srgb_u8(100, 100, 100), &mut materials);
+    }
+}
+
+/// Move objects that left a wrapping boundary back in on the other side
+pub fn wrap_around(
+    boundary: Res<WorldBoundary>,
+    mut query: Query<&mut Position, With<PhysicsObject>>,
+) {
+    if boundary.kind != BoundaryKind::Wrap {
+        return;
+    }
+
+    let half_size = DVec2::from(boundary.half_size);
+    for mut position in &mut query {
+        // only objects outside are moved, so the others aren't touched by
+        // rounding errors
+        let outside = position.0.abs().cmpgt(half_size);
+        if outside.any() {
+            let wrapped = (position.0 + half_size).rem_euclid(2.0 * half_size) - half_size;
+            position.0 = DVec2::select(outside, wrapped, position.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn boundary(kind: BoundaryKind) -> WorldBoundary {
+        WorldBoundary {
+            kind,
+            half_size: [2.0, 1.0],
+        }
+    }
+
+    fn wrapped(kind: BoundaryKind, positions: &[DVec2]) -> Vec<DVec2> {
+        let mut world = World::new();
+        world.insert_resource(boundary(kind));
+        let entities: Vec<_> = positions
+            .iter()
+            .map(|position| {
+                world
+                    .spawn((Position(*position), PhysicsObject::at_rest(1.0)))
+                    .id()
+            })
+            .collect();
+        world.run_system_once(wrap_around).unwrap();
+        entities
+            .into_iter()
+            .map(|entity| world.get::<Position>(entity).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let outside = [
+            DVec2::new(2.5, 0.3),
+            DVec2::new(-0.1, -1.25),
+            DVec2::new(-6.5, 1.5),
+        ];
+        assert_eq!(
+            wrapped(BoundaryKind::Wrap, &outside),
+            [
+                DVec2::new(-1.5, 0.3),
+                DVec2::new(-0.1, 0.75),
+                DVec2::new(1.5, -0.5),
+            ]
+        );
+        // other boundaries leave them where they are
+        assert_eq!(wrapped(BoundaryKind::Box, &outside), outside);
+    }
+
+    /// Objects inside are left exactly where they are, instead of being
+    /// moved by the rounding errors of wrapping them
+    #[test]
+    fn test_inside_is_untouched() {
+        let inside = [
+            DVec2::new(0.1 + 0.2, -0.7),
+            DVec2::new(-1.9999999, 0.9999999),
+            DVec2::new(2.0, -1.0),
+            DVec2::new(1.0 / 3.0, 0.0),
+        ];
+        let after = wrapped(BoundaryKind::Wrap, &inside);
+        for (before, after) in inside.iter().zip(after) {
+            assert_eq!(
+                before.to_array().map(f64::to_bits),
+                after.to_array().map(f64::to_bits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_walls() {
+        let floor = (DVec2::new(0.0, -1.5), DVec2::new(6.0, 1.0));
+        let ceiling = (DVec2::new(0.0, 1.5), DVec2::new(6.0, 1.0));
+        let left = (DVec2::new(-2.5, 0.0), DVec2::new(1.0, 2.0));
+        let right = (DVec2::new(2.5, 0.0), DVec2::new(1.0, 2.0));
+        for (kind, walls) in [
+            (BoundaryKind::None, vec![]),
+            (BoundaryKind::Floor, vec![floor]),
+            (BoundaryKind::Walls, vec![left, right]),
+            (BoundaryKind::Box, vec![floor, ceiling, left, right]),
+            (BoundaryKind::Wrap, vec![]),
+        ] {
+            assert_eq!(boundary(kind).walls(), walls, "{kind}");
+        }
+    }
+}
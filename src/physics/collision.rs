@@ -1,40 +1,531 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
+use bevy::math::DVec2;
 use bevy::prelude::*;
 
-use crate::components::{PhysicsObject, Position, Rotation, Size, Tangible};
-use crate::shapes::{CollisionData, Shape, ShapeImpl};
+use crate::components::{
+    CollisionLayers, Connection, IgnoreCollisions, PhysicsMaterial, PhysicsObject, Position,
+    Rotation, Size, Tangible,
+};
+use crate::shapes::{
+    CollisionData, Compound, Shape, ShapeImpl, combined_bounding_box, placed_shapes,
+};
+use crate::utils::sweep_and_prune;
 
-pub fn apply_collision_force(
-    mut physics_query: Query<&mut PhysicsObject>,
-    shape_query: Query<(Entity, &Shape, &Position, &Size, &Rotation), With<Tangible>>,
-) {
-    let mut collision_data_map: HashMap<Entity, Vec<CollisionData>> = HashMap::new();
+/// How many times the contact impulses are refined each step
+const SOLVER_ITERATIONS: usize = 8;
+/// Bodies approaching slower than this don't bounce, which lets them come to rest
+const RESTITUTION_THRESHOLD: f64 = 0.1;
+/// How deep bodies can overlap before we push them apart
+const PENETRATION_SLOP: f64 = 0.005;
+/// How much of the overlap is removed each step
+const CORRECTION_PERCENT: f64 = 0.4;
 
-    for (entity1, shape1, position1, size1, rotation1) in &shape_query {
-        let data1 = (*position1, *size1, *rotation1).into();
-        for (entity2, shape2, position2, size2, rotation2) in &shape_query {
-            if entity1 == entity2 {
-                continue;
-            }
-            let data2 = (*position2, *size2, *rotation2).into();
+type ShapeQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        (Option<&'static Shape>, Option<&'static Compound>),
+        &'static mut Position,
+        &'static Size,
+        &'static Rotation,
+        Option<&'static PhysicsMaterial>,
+        Option<&'static CollisionLayers>,
+        Option<&'static Connection>,
+    ),
+    (With<Tangible>, Or<(With<Shape>, With<Compound>)>),
+>;
+
+/// Pairs of entities that never collide, with the smaller entity first
+struct IgnoredPairs(HashSet<(Entity, Entity)>);
+
+impl IgnoredPairs {
+    fn new(query: &Query<&Connection, With<IgnoreCollisions>>) -> Self {
+        Self(
+            query
+                .iter()
+                .map(|connection| Self::key(connection.entity1, connection.entity2))
+                .collect(),
+        )
+    }
+
+    fn key(entity1: Entity, entity2: Entity) -> (Entity, Entity) {
+        (entity1.min(entity2), entity1.max(entity2))
+    }
+
+    fn contains(&self, entity1: Entity, entity2: Entity) -> bool {
+        self.0.contains(&Self::key(entity1, entity2))
+    }
+}
+
+/// A tangible spring or joint never collides with the entities it connects
+fn connects(connection: Option<&Connection>, entity: Entity) -> bool {
+    connection
+        .is_some_and(|connection| connection.entity1 == entity || connection.entity2 == entity)
+}
+
+/// The mass properties of a body, as seen by the contact solver
+#[derive(Clone, Copy)]
+struct Body {
+    entity: Entity,
+    center: DVec2,
+    inverse_mass: f64,
+    inverse_moment_of_inertia: f64,
+}
+
+impl Body {
+    /// Objects without a `PhysicsObject` can't be moved, so they have infinite
+    /// mass, just like static and kinematic objects
+    fn new(entity: Entity, center: DVec2, physics_query: &Query<&mut PhysicsObject>) -> Self {
+        let (inverse_mass, inverse_moment_of_inertia) =
+            physics_query
+                .get(entity)
+                .map_or((0.0, 0.0), |physics_object| {
+                    (
+                        physics_object.inverse_mass(),
+                        physics_object.inverse_moment_of_inertia(),
+                    )
+                });
+
+        Self {
+            entity,
+            center,
+            inverse_mass,
+            inverse_moment_of_inertia,
+        }
+    }
+
+    /// How much the velocity at `offset` along `direction` changes per unit impulse
+    fn inverse_effective_mass(&self, offset: DVec2, direction: DVec2) -> f64 {
+        self.inverse_mass + self.inverse_moment_of_inertia * offset.perp_dot(direction).powi(2)
+    }
+
+    fn velocity_at(&self, physics_query: &Query<&mut PhysicsObject>, offset: DVec2) -> DVec2 {
+        physics_query
+            .get(self.entity)
+            .map_or(DVec2::ZERO, |physics_object| {
+                physics_object.velocity_at(offset)
+            })
+    }
+
+    fn apply_impulse(
+        &self,
+        physics_query: &mut Query<&mut PhysicsObject>,
+        impulse: DVec2,
+        offset: DVec2,
+    ) {
+        if let Ok(mut physics_object) = physics_query.get_mut(self.entity) {
+            physics_object.apply_impulse_at(impulse, offset);
+        }
+    }
+}
+
+struct ContactPoint {
+    /// Offset from the center of body1
+    offset1: DVec2,
+    /// Offset from the center of body2
+    offset2: DVec2,
+    normal_mass: f64,
+    tangent_mass: f64,
+    /// Normal velocity we want after the collision is resolved
+    target_velocity: f64,
+    normal_impulse: f64,
+    tangent_impulse: f64,
+}
+
+struct Contact {
+    body1: Body,
+    body2: Body,
+    /// Points from body2 towards body1
+    normal: DVec2,
+    depth: f64,
+    friction: f64,
+    /// Part of the positional correction of the two bodies this contact
+    /// applies. Compounds can touch a body with several children at once,
+    /// and the bodies should only be pushed apart once.
+    correction_share: f64,
+    points: Vec<ContactPoint>,
+}
+
+impl Contact {
+    /// A contact from where two shapes of the bodies collide. Shapes without
+    /// contact points push apart from the point between their centers.
+    fn new(
+        body1: Body,
+        body2: Body,
+        collision_data: &CollisionData,
+        centers: (DVec2, DVec2),
+        (restitution, friction): (f64, f64),
+        physics_query: &Query<&mut PhysicsObject>,
+    ) -> Self {
+        let normal = collision_data.direction.as_dvec2();
+        let tangent = normal.perp();
+
+        let mut contact_points: Vec<_> = collision_data
+            .contact_points
+            .as_slice()
+            .iter()
+            .map(|point| point.as_dvec2())
+            .collect();
+        if contact_points.is_empty() {
+            // without contact points, we can only push the centers apart
+            contact_points.push((centers.0 + centers.1) / 2.0);
+        }
+
+        let mut contact = Self {
+            body1,
+            body2,
+            normal,
+            depth: f64::from(collision_data.depth),
+            friction,
+            correction_share: 1.0,
+            points: Vec::with_capacity(contact_points.len()),
+        };
 
-            if let Some(collision_data) = shape1.collides_with_shape(&data1, shape2, &data2) {
-                let entry = collision_data_map.entry(entity1).or_default();
-                entry.push(collision_data);
+        for point in contact_points {
+            let offset1 = point - body1.center;
+            let offset2 = point - body2.center;
+
+            let mut contact_point = ContactPoint {
+                offset1,
+                offset2,
+                normal_mass: 1.0
+                    / (body1.inverse_effective_mass(offset1, normal)
+                        + body2.inverse_effective_mass(offset2, normal)),
+                tangent_mass: 1.0
+                    / (body1.inverse_effective_mass(offset1, tangent)
+                        + body2.inverse_effective_mass(offset2, tangent)),
+                target_velocity: 0.0,
+                normal_impulse: 0.0,
+                tangent_impulse: 0.0,
+            };
+
+            let normal_velocity = contact
+                .relative_velocity(physics_query, &contact_point)
+                .dot(normal);
+            if normal_velocity < -RESTITUTION_THRESHOLD {
+                contact_point.target_velocity = -restitution * normal_velocity;
             }
+
+            contact.points.push(contact_point);
         }
+
+        contact
+    }
+
+    fn relative_velocity(
+        &self,
+        physics_query: &Query<&mut PhysicsObject>,
+        point: &ContactPoint,
+    ) -> DVec2 {
+        self.body1.velocity_at(physics_query, point.offset1)
+            - self.body2.velocity_at(physics_query, point.offset2)
     }
 
-    for (entity, collision_data_lists) in collision_data_map {
-        let Ok(mut physics_object) = physics_query.get_mut(entity) else {
+    fn apply_impulse(
+        &self,
+        physics_query: &mut Query<&mut PhysicsObject>,
+        point: &ContactPoint,
+        impulse: DVec2,
+    ) {
+        self.body1
+            .apply_impulse(physics_query, impulse, point.offset1);
+        self.body2
+            .apply_impulse(physics_query, -impulse, point.offset2);
+    }
+}
+
+/// Find where tangible objects touch. Compounds collide child by child, and
+/// each pair of children that touch makes its own contact. Those contacts
+/// share the positional correction of the pair.
+fn find_contacts(
+    shape_query: &ShapeQuery,
+    physics_query: &Query<&mut PhysicsObject>,
+    ignored_pairs: &IgnoredPairs,
+) -> Vec<Contact> {
+    let placed: Vec<_> = shape_query
+        .iter()
+        .map(
+            |(entity, (shape, compound), position, size, rotation, ..)| {
+                let data = (*position, *size, *rotation).into();
+                (entity, placed_shapes(shape, compound, data))
+            },
+        )
+        .collect();
+    let bounding_boxes: Vec<_> = placed
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (_, shapes))| Some((i, combined_bounding_box(shapes)?)))
+        .collect();
+
+    let mut contacts = Vec::new();
+
+    for (i, j) in sweep_and_prune(&bounding_boxes) {
+        let ((entity1, shapes1), (entity2, shapes2)) = (&placed[i], &placed[j]);
+        let (entity1, entity2) = (*entity1, *entity2);
+        let Ok(
+            [
+                (_, _, position1, _, _, material1, layers1, connection1),
+                (_, _, position2, _, _, material2, layers2, connection2),
+            ],
+        ) = shape_query.get_many([entity1, entity2])
+        else {
             continue;
         };
 
-        let mass = physics_object.mass;
-        for collision_data in collision_data_lists {
-            physics_object.acceleration +=
-                (100.0 * collision_data.depth * collision_data.direction).as_dvec2() / mass;
+        let layers1 = layers1.copied().unwrap_or_default();
+        let layers2 = layers2.copied().unwrap_or_default();
+        if !layers1.interacts_with(&layers2)
+            || ignored_pairs.contains(entity1, entity2)
+            || connects(connection1, entity2)
+            || connects(connection2, entity1)
+        {
+            continue;
         }
+
+        let body1 = Body::new(entity1, position1.0, physics_query);
+        let body2 = Body::new(entity2, position2.0, physics_query);
+        if body1.inverse_mass + body2.inverse_mass == 0.0 {
+            // neither object can move, so there is nothing to resolve
+            continue;
+        }
+
+        let material1 = material1.copied().unwrap_or_default();
+        let material2 = material2.copied().unwrap_or_default();
+        let restitution = material1.restitution.max(material2.restitution);
+        let friction = (material1.friction * material2.friction).sqrt();
+
+        let first = contacts.len();
+        for (shape1, data1) in shapes1 {
+            for (shape2, data2) in shapes2 {
+                let Some(collision_data) = shape1.collides_with_shape(data1, shape2, data2) else {
+                    continue;
+                };
+                contacts.push(Contact::new(
+                    body1,
+                    body2,
+                    &collision_data,
+                    (data1.position, data2.position),
+                    (restitution, friction),
+                    physics_query,
+                ));
+            }
+        }
+        let pair_contacts = &mut contacts[first..];
+        let correction_share = (pair_contacts.len() as f64).recip();
+        for contact in pair_contacts {
+            contact.correction_share = correction_share;
+        }
+    }
+
+    contacts
+}
+
+fn solve_velocities(contacts: &mut [Contact], physics_query: &mut Query<&mut PhysicsObject>) {
+    for _ in 0..SOLVER_ITERATIONS {
+        for contact in contacts.iter_mut() {
+            let normal = contact.normal;
+            let tangent = normal.perp();
+
+            let mut points = std::mem::take(&mut contact.points);
+            for point in &mut points {
+                // normal impulse. The accumulated impulse must be positive,
+                // as bodies can only push each other apart
+                let normal_velocity = contact.relative_velocity(physics_query, point).dot(normal);
+                let impulse = (point.target_velocity - normal_velocity) * point.normal_mass;
+                let previous_impulse = point.normal_impulse;
+                point.normal_impulse = (previous_impulse + impulse).max(0.0);
+                let impulse = (point.normal_impulse - previous_impulse) * normal;
+                contact.apply_impulse(physics_query, point, impulse);
+
+                // friction impulse, limited by the Coulomb friction cone
+                let tangent_velocity = contact.relative_velocity(physics_query, point).dot(tangent);
+                let impulse = -tangent_velocity * point.tangent_mass;
+                let max_impulse = contact.friction * point.normal_impulse;
+                let previous_impulse = point.tangent_impulse;
+                point.tangent_impulse =
+                    (previous_impulse + impulse).clamp(-max_impulse, max_impulse);
+                let impulse = (point.tangent_impulse - previous_impulse) * tangent;
+                contact.apply_impulse(physics_query, point, impulse);
+            }
+            contact.points = points;
+        }
+    }
+}
+
+fn correct_positions(contacts: &[Contact], shape_query: &mut ShapeQuery) {
+    for contact in contacts {
+        let (body1, body2) = (contact.body1, contact.body2);
+        let correction = (contact.depth - PENETRATION_SLOP).max(0.0)
+            * CORRECTION_PERCENT
+            * contact.correction_share
+            / (body1.inverse_mass + body2.inverse_mass)
+            * contact.normal;
+
+        if let Ok((_, _, mut position, ..)) = shape_query.get_mut(body1.entity) {
+            position.0 += correction * body1.inverse_mass;
+        }
+        if let Ok((_, _, mut position, ..)) = shape_query.get_mut(body2.entity) {
+            position.0 -= correction * body2.inverse_mass;
+        }
+    }
+}
+
+/// Resolve collisions between tangible objects by applying impulses, then push
+/// overlapping objects apart. Objects only collide if their `CollisionLayers`
+/// allow it and they aren't connected by something with `IgnoreCollisions`.
+pub fn resolve_collisions(
+    mut shape_query: ShapeQuery,
+    mut physics_query: Query<&mut PhysicsObject>,
+    ignore_query: Query<&Connection, With<IgnoreCollisions>>,
+) {
+    let ignored_pairs = IgnoredPairs::new(&ignore_query);
+    let mut contacts = find_contacts(&shape_query, &physics_query, &ignored_pairs);
+
+    solve_velocities(&mut contacts, &mut physics_query);
+    correct_positions(&contacts, &mut shape_query);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::components::BodyKind;
+
+    /// A static 4×1 floor at the origin
+    fn spawn_floor(world: &mut World) -> Entity {
+        world
+            .spawn((
+                Shape::Square,
+                Position(DVec2::ZERO),
+                Size {
+                    width: 4.0,
+                    height: 1.0,
+                },
+                PhysicsObject {
+                    kind: BodyKind::Static,
+                    ..PhysicsObject::at_rest(1.0)
+                },
+                Tangible,
+            ))
+            .id()
+    }
+
+    /// A unit square sinking 0.1 into the floor at `x`
+    fn spawn_block(world: &mut World, x: f64) -> Entity {
+        world
+            .spawn((
+                Shape::Square,
+                Position(DVec2::new(x, 0.9)),
+                Size {
+                    width: 1.0,
+                    height: 1.0,
+                },
+                PhysicsObject::at_rest(1.0),
+                Tangible,
+            ))
+            .id()
+    }
+
+    fn pushed_out(world: &mut World, block: Entity) -> bool {
+        world.run_system_once(resolve_collisions).unwrap();
+        world.get::<Position>(block).unwrap().y > 0.9
+    }
+
+    #[test]
+    fn test_collision_layers() {
+        for (filter, collides) in [(0b01, true), (0b10, false)] {
+            let mut world = World::new();
+            let floor = spawn_floor(&mut world);
+            world.entity_mut(floor).insert(CollisionLayers {
+                memberships: 0b01,
+                filter: 0b11,
+            });
+            let block = spawn_block(&mut world, 0.0);
+            world.entity_mut(block).insert(CollisionLayers {
+                memberships: 0b10,
+                filter,
+            });
+            assert_eq!(pushed_out(&mut world, block), collides);
+        }
+    }
+
+    #[test]
+    fn test_ignore_collisions() {
+        let mut world = World::new();
+        let floor = spawn_floor(&mut world);
+        let block = spawn_block(&mut world, 0.0);
+        world.spawn((
+            Connection {
+                entity1: block,
+                entity2: floor,
+            },
+            IgnoreCollisions,
+        ));
+        assert!(!pushed_out(&mut world, block));
+    }
+
+    /// A tangible connector pushes other bodies, but not the ones it connects
+    #[test]
+    fn test_tangible_connector() {
+        let mut world = World::new();
+        let floor = spawn_floor(&mut world);
+        let connected = spawn_block(&mut world, -1.0);
+        let other = spawn_block(&mut world, 1.0);
+        // the connector takes the place of the floor
+        world.entity_mut(floor).remove::<Tangible>();
+        world.spawn((
+            Shape::Square,
+            Position(DVec2::ZERO),
+            Size {
+                width: 4.0,
+                height: 1.0,
+            },
+            Connection {
+                entity1: connected,
+                entity2: floor,
+            },
+            Tangible,
+        ));
+        world.run_system_once(resolve_collisions).unwrap();
+        assert_eq!(world.get::<Position>(connected).unwrap().y, 0.9);
+        assert!(world.get::<Position>(other).unwrap().y > 0.9);
+    }
+
+    /// Each child of a compound touching the same body shouldn't push it out
+    /// again, or the compound jumps out of the body
+    #[test]
+    fn test_compound_is_pushed_out_once() {
+        let mut world = World::new();
+        spawn_floor(&mut world);
+
+        let penetration = 0.1;
+        let height = 1.0 - penetration;
+        let compound = world
+            .spawn((
+                ron::from_str::<Compound>(
+                    "[
+                        (shape: Square, offset: (-1.0, 0.0), mass: 1.0),
+                        (shape: Square, offset: (0.0, 0.0), mass: 1.0),
+                        (shape: Square, offset: (1.0, 0.0), mass: 1.0),
+                    ]",
+                )
+                .unwrap(),
+                Position(DVec2::new(0.0, height)),
+                Size {
+                    width: 1.0,
+                    height: 1.0,
+                },
+                PhysicsObject::at_rest(3.0),
+                Tangible,
+            ))
+            .id();
+
+        world.run_system_once(resolve_collisions).unwrap();
+        let correction = world.get::<Position>(compound).unwrap().y - height;
+        assert!(correction > 0.0);
+        assert!(correction <= penetration);
     }
 }
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::drag::{AirResistance, DragQuery, drag_force};
+use super::spring::spring_damping_power;
+use crate::components::{Connection, PhysicsObject, Position, Spring, SpringForce};
+
+/// Energy removed from the simulation by drag, spring damping and collisions.
+/// Adding it to the total energy should give a constant.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DissipatedEnergy {
+    /// Energy lost since the scene started
+    pub total: f64,
+    /// Rate of loss from drag and damping at the start of the step
+    #[serde(skip)]
+    power: f64,
+    /// Kinetic energy just before collisions were resolved
+    #[serde(skip)]
+    kinetic_energy: f64,
+}
+
+fn kinetic_energy(query: &Query<&PhysicsObject>) -> f64 {
+    query
+        .iter()
+        .map(|physics_object| physics_object.kinetic_energy())
+        .sum()
+}
+
+/// The rate at which drag and spring damping are removing energy
+fn dissipated_power(
+    air_resistance: &AirResistance,
+    drag_query: &DragQuery<&PhysicsObject>,
+    spring_query: &Query<(&SpringForce, &Connection)>,
+    position_query: &Query<&Position, Without<Spring>>,
+) -> f64 {
+    let drag_power: f64 = drag_query
+        .iter()
+        .map(|(physics_object, drag, shape)| {
+            -drag_force(air_resistance, physics_object, drag, shape).dot(physics_object.velocity)
+        })
+        .sum();
+
+    let damping_power = spring_damping_power(spring_query, position_query, |entity| {
+        drag_query
+            .get(entity)
+            .ok()
+            .map(|(physics_object, _, _)| physics_object.velocity)
+    });
+
+    drag_power + damping_power
+}
+
+pub fn measure_dissipated_power(
+    air_resistance: Res<AirResistance>,
+    mut dissipated_energy: ResMut<DissipatedEnergy>,
+    drag_query: DragQuery<&PhysicsObject>,
+    spring_query: Query<(&SpringForce, &Connection)>,
+    position_query: Query<&Position, Without<Spring>>,
+) {
+    dissipated_energy.power =
+        dissipated_power(&air_resistance, &drag_query, &spring_query, &position_query);
+}
+
+pub fn measure_kinetic_energy(
+    mut dissipated_energy: ResMut<DissipatedEnergy>,
+    query: Query<&PhysicsObject>,
+) {
+    dissipated_energy.kinetic_energy = kinetic_energy(&query);
+}
+
+/// Add up the energy lost during the step. Collisions change the velocities
+/// instantly, so their loss is the change in kinetic energy, while drag and
+/// damping are integrated over the step with the trapezoidal rule.
+pub fn track_dissipated_energy(
+    time: Res<Time>,
+    air_resistance: Res<AirResistance>,
+    mut dissipated_energy: ResMut<DissipatedEnergy>,
+    query: Query<&PhysicsObject>,
+    drag_query: DragQuery<&PhysicsObject>,
+    spring_query: Query<(&SpringForce, &Connection)>,
+    position_query: Query<&Position, Without<Spring>>,
+) {
+    let collision_loss = dissipated_energy.kinetic_energy - kinetic_energy(&query);
+
+    let power = dissipated_power(&air_resistance, &drag_query, &spring_query, &position_query);
+    let drag_loss = 0.5 * (dissipated_energy.power + power) * time.delta_secs_f64();
+
+    dissipated_energy.total += collision_loss + drag_loss;
+}
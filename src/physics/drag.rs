@@ -0,0 +1,100 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::{PhysicsObject, Rotation, Size};
+use crate::shapes::{Compound, Shape, ShapeData, ShapeImpl, placed_shapes};
+
+/// A force against the velocity of a physics object
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Drag {
+    /// Force per unit of speed
+    #[serde(default)]
+    pub linear: f64,
+    /// Force per unit of speed squared
+    #[serde(default)]
+    pub quadratic: f64,
+    /// Multiply the force by the width of the shape across the direction of
+    /// motion, so objects moving broadside on are slowed down more
+    #[serde(default)]
+    pub scale_with_width: bool,
+}
+
+/// Drag felt by every physics object, on top of their own `Drag`
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AirResistance(pub Drag);
+
+impl Drag {
+    fn force(&self, velocity: DVec2, width: impl FnOnce() -> f64) -> DVec2 {
+        if self.linear == 0.0 && self.quadratic == 0.0 {
+            return DVec2::ZERO;
+        }
+        let scale = if self.scale_with_width { width() } else { 1.0 };
+        -scale * (self.linear + self.quadratic * velocity.length()) * velocity
+    }
+}
+
+/// The shape of an object, or the shapes of a compound
+type ShapeOf<'a> = (
+    Option<&'a Shape>,
+    Option<&'a Compound>,
+    &'a Size,
+    &'a Rotation,
+);
+
+/// Width of the shape across `direction`, found from its vertices. Objects
+/// without a shape are treated as being one unit wide.
+fn projected_width(shape: Option<ShapeOf>, direction: DVec2) -> f64 {
+    let Some((shape, compound, size, rotation)) = shape else {
+        return 1.0;
+    };
+    let data = ShapeData {
+        position: DVec2::ZERO,
+        rotation: rotation.0,
+        size: DVec2::from(*size),
+    };
+    let shapes = placed_shapes(shape, compound, data);
+    if shapes.is_empty() {
+        return 1.0;
+    }
+
+    let across = direction.perp();
+    let (min, max) = shapes
+        .iter()
+        .flat_map(|(shape, data)| shape.get_shape_vertices(data))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), vertex| {
+            let projection = across.dot(vertex.as_dvec2());
+            (min.min(projection), max.max(projection))
+        });
+    max - min
+}
+
+pub type DragQuery<'w, 's, T> = Query<'w, 's, (T, Option<&'static Drag>, Option<ShapeOf<'static>>)>;
+
+/// The total drag force on a physics object
+pub fn drag_force(
+    air_resistance: &AirResistance,
+    physics_object: &PhysicsObject,
+    drag: Option<&Drag>,
+    shape: Option<ShapeOf>,
+) -> DVec2 {
+    let velocity = physics_object.velocity;
+    if velocity == DVec2::ZERO {
+        return DVec2::ZERO;
+    }
+
+    let width = || projected_width(shape, velocity.normalize());
+    let mut force = air_resistance.0.force(velocity, width);
+    if let Some(drag) = drag {
+        force += drag.force(velocity, width);
+    }
+    force
+}
+
+pub fn apply_drag(air_resistance: Res<AirResistance>, mut query: DragQuery<&mut PhysicsObject>) {
+    for (mut physics_object, drag, shape) in &mut query {
+        let force = drag_force(&air_resistance, &physics_object, drag, shape);
+        let mass = physics_object.mass;
+        physics_object.acceleration += force / mass;
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 
 use crate::components::{Connection, PhysicsObject, Position, Spring, SpringForce};
+use crate::physics::gravity::{Attractor, GravityBodyQuery, MutualGravity};
+use crate::physics::{DissipatedEnergy, Gravity, Integrators};
 use crate::{Energy, EnergyFile};
 
 use crate::physics::gravity::gravitational_potential_energy;
@@ -8,21 +10,32 @@ use crate::physics::spring::spring_potential_energy;
 
 use std::{fs::OpenOptions, io::Write};
 
+#[allow(clippy::too_many_arguments)]
 pub fn calculate_total_energy(
     timer: Res<Time>,
     mut total_energy_resource: ResMut<Energy>,
     energy_file_resource: Res<EnergyFile>,
+    integrator: Res<Integrators>,
+    dissipated_energy: Res<DissipatedEnergy>,
+    gravity: Res<Gravity>,
+    mutual_gravity: Option<Res<MutualGravity>>,
     spring_query: Query<(&SpringForce, &Connection)>,
     position_query: Query<&Position, Without<Spring>>,
-    body_query: Query<(&Position, &PhysicsObject)>,
+    attractor_query: Query<(&Position, &Attractor)>,
+    body_query: GravityBodyQuery<&PhysicsObject>,
 ) {
     // calculate kinetic energy
-    let mut total_energy = body_query.iter().fold(0.0, |acc, (_, physics_object)| {
-        acc + 0.5 * physics_object.mass * physics_object.velocity.length_squared()
+    let mut total_energy = body_query.iter().fold(0.0, |acc, (_, physics_object, _)| {
+        acc + physics_object.kinetic_energy()
     });
 
     // calculate potential energies
-    total_energy += gravitational_potential_energy(body_query);
+    total_energy += gravitational_potential_energy(
+        &gravity,
+        mutual_gravity.as_deref(),
+        &attractor_query,
+        &body_query,
+    );
     total_energy += spring_potential_energy(spring_query, position_query);
 
     // this should hopefully not happen :)
@@ -42,11 +55,16 @@ pub fn calculate_total_energy(
             .append(true)
             .open(filename)
             .expect("energy file should exist");
+        if integrator.is_changed() {
+            // record which integrator produced the following energies
+            writeln!(file, "# integrator: {}", *integrator).expect("energy file should exist");
+        }
         writeln!(
             file,
-            "{:.15} {:.15}",
+            "{:.15} {:.15} {:.15}",
             timer.elapsed_secs_f64(),
-            total_energy
+            total_energy,
+            dissipated_energy.total
         )
         .expect("energy file should exist");
     }
@@ -1,19 +1,271 @@
 use bevy::math::DVec2;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::components::{PhysicsObject, Position};
+use crate::utils::BarnesHutTree;
 
-// g = π²
-const GRAVITY: f64 = 9.81;
+/// Gravitational acceleration felt by every physics object
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Gravity(pub DVec2);
 
-pub fn apply_gravity(mut query: Query<&mut PhysicsObject>) {
-    for mut physics_component in &mut query {
-        physics_component.acceleration -= GRAVITY * DVec2::Y;
+impl Default for Gravity {
+    fn default() -> Self {
+        // g = π²
+        Self(DVec2::new(0.0, -9.81))
     }
 }
 
-pub fn gravitational_potential_energy(query: Query<(&Position, &PhysicsObject)>) -> f64 {
-    query.iter().fold(0.0, |acc, (position, physics_object)| {
-        acc + physics_object.mass * GRAVITY * position.0.y
-    })
+/// Scales every kind of gravity acting on a physics object. Objects without
+/// it feel gravity normally, and a scale of zero opts out of gravity entirely.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GravityScale(pub f64);
+
+impl Default for GravityScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// A fixed point mass that pulls every physics object towards it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Attractor {
+    /// The gravitational constant times the mass of the attractor
+    pub strength: f64,
+    /// Keeps the pull finite when an object gets close to the attractor
+    #[serde(default)]
+    pub softening: f64,
+}
+
+/// Gravitation between every pair of physics objects. Only active while the
+/// resource exists.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MutualGravity {
+    /// The gravitational constant
+    pub constant: f64,
+    /// Keeps the pull finite when two objects get close to each other
+    #[serde(default)]
+    pub softening: f64,
+    /// Opening angle of the Barnes–Hut approximation. Groups of objects that
+    /// are smaller than `theta` times their distance pull as a single object.
+    /// Zero sums every pair exactly.
+    #[serde(default)]
+    pub theta: f64,
+}
+
+/// `1 / r` for an offset of length `r`, softened so it stays finite at zero
+fn inverse_distance(offset: DVec2, softening: f64) -> f64 {
+    (offset.length_squared() + softening * softening)
+        .sqrt()
+        .recip()
+}
+
+/// Acceleration towards a point mass of `strength` at `offset`. This is the
+/// gradient of the softened potential `-strength / r`.
+fn point_mass_acceleration(offset: DVec2, strength: f64, softening: f64) -> DVec2 {
+    strength * inverse_distance(offset, softening).powi(3) * offset
+}
+
+fn scale_of(scale: Option<&GravityScale>) -> f64 {
+    scale.map_or(1.0, |scale| scale.0)
+}
+
+/// A tree of every object, in query order, weighted by its gravity scale
+fn barnes_hut_tree<'a>(
+    bodies: impl Iterator<Item = (&'a Position, &'a PhysicsObject, Option<&'a GravityScale>)>,
+) -> BarnesHutTree {
+    BarnesHutTree::new(
+        bodies
+            .map(|(position, physics_object, scale)| {
+                (position.0, scale_of(scale) * physics_object.mass)
+            })
+            .collect(),
+    )
+}
+
+pub type GravityBodyQuery<'w, 's, T> =
+    Query<'w, 's, (&'static Position, T, Option<&'static GravityScale>)>;
+
+pub fn apply_gravity(
+    gravity: Res<Gravity>,
+    mutual_gravity: Option<Res<MutualGravity>>,
+    attractors: Query<(&Position, &Attractor)>,
+    mut query: GravityBodyQuery<&mut PhysicsObject>,
+) {
+    for (position, mut physics_component, scale) in &mut query {
+        let scale = scale_of(scale);
+        if scale == 0.0 {
+            continue;
+        }
+
+        let mut acceleration = gravity.0;
+        for (attractor_position, attractor) in &attractors {
+            acceleration += point_mass_acceleration(
+                attractor_position.0 - position.0,
+                attractor.strength,
+                attractor.softening,
+            );
+        }
+        physics_component.acceleration += scale * acceleration;
+    }
+
+    let Some(mutual_gravity) = mutual_gravity else {
+        return;
+    };
+    if mutual_gravity.theta > 0.0 {
+        let tree = barnes_hut_tree(query.iter());
+        for (index, (_, mut physics_component, scale)) in query.iter_mut().enumerate() {
+            let mut pull = DVec2::ZERO;
+            tree.visit(index, mutual_gravity.theta, |offset, mass| {
+                pull += point_mass_acceleration(offset, mass, mutual_gravity.softening);
+            });
+            physics_component.acceleration += scale_of(scale) * mutual_gravity.constant * pull;
+        }
+    } else {
+        let mut pairs = query.iter_combinations_mut();
+        while let Some(
+            [
+                (position1, mut body1, scale1),
+                (position2, mut body2, scale2),
+            ],
+        ) = pairs.fetch_next()
+        {
+            // the scales multiply so the forces stay equal and opposite
+            let scale = scale_of(scale1) * scale_of(scale2);
+            if scale == 0.0 {
+                continue;
+            }
+            let pull = point_mass_acceleration(
+                position2.0 - position1.0,
+                scale * mutual_gravity.constant,
+                mutual_gravity.softening,
+            );
+            body1.acceleration += body2.mass * pull;
+            body2.acceleration -= body1.mass * pull;
+        }
+    }
+}
+
+/// The potential energy of whichever gravity is active, matching the forces
+/// from `apply_gravity`
+pub fn gravitational_potential_energy(
+    gravity: &Gravity,
+    mutual_gravity: Option<&MutualGravity>,
+    attractors: &Query<(&Position, &Attractor)>,
+    query: &GravityBodyQuery<&PhysicsObject>,
+) -> f64 {
+    let mut energy = query
+        .iter()
+        .fold(0.0, |acc, (position, physics_object, scale)| {
+            let attractor_potential = attractors
+                .iter()
+                .map(|(attractor_position, attractor)| {
+                    -attractor.strength
+                        * inverse_distance(attractor_position.0 - position.0, attractor.softening)
+                })
+                .sum::<f64>();
+            let potential = -gravity.0.dot(position.0) + attractor_potential;
+            acc + scale_of(scale) * physics_object.mass * potential
+        });
+
+    let Some(mutual_gravity) = mutual_gravity else {
+        return energy;
+    };
+    if mutual_gravity.theta > 0.0 {
+        let tree = barnes_hut_tree(query.iter());
+        for (index, (_, physics_object, scale)) in query.iter().enumerate() {
+            let mut potential = 0.0;
+            tree.visit(index, mutual_gravity.theta, |offset, mass| {
+                potential -= mass * inverse_distance(offset, mutual_gravity.softening);
+            });
+            // every pair is counted from both sides
+            energy +=
+                0.5 * mutual_gravity.constant * scale_of(scale) * physics_object.mass * potential;
+        }
+    } else {
+        for [(position1, body1, scale1), (position2, body2, scale2)] in query.iter_combinations() {
+            energy -= mutual_gravity.constant
+                * scale_of(scale1)
+                * scale_of(scale2)
+                * body1.mass
+                * body2.mass
+                * inverse_distance(position2.0 - position1.0, mutual_gravity.softening);
+        }
+    }
+
+    energy
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn potential_energy(world: &mut World) -> f64 {
+        world
+            .run_system_once(
+                |gravity: Res<Gravity>,
+                 mutual_gravity: Option<Res<MutualGravity>>,
+                 attractors: Query<(&Position, &Attractor)>,
+                 query: GravityBodyQuery<&PhysicsObject>| {
+                    gravitational_potential_energy(
+                        &gravity,
+                        mutual_gravity.as_deref(),
+                        &attractors,
+                        &query,
+                    )
+                },
+            )
+            .unwrap()
+    }
+
+    /// The forces should be the negative gradient of the potential energy,
+    /// otherwise the energy readout drifts even when the integrator is exact
+    #[test]
+    fn test_forces_match_potential_energy() {
+        let mut world = World::new();
+        world.insert_resource(Gravity(DVec2::new(1.0, -3.0)));
+        world.insert_resource(MutualGravity {
+            constant: 2.0,
+            softening: 0.1,
+            theta: 0.0,
+        });
+        world.spawn((
+            Position(DVec2::new(-1.0, 2.0)),
+            Attractor {
+                strength: 5.0,
+                softening: 0.2,
+            },
+        ));
+        let body = world
+            .spawn((
+                Position(DVec2::new(0.5, 0.25)),
+                PhysicsObject::at_rest(2.0),
+                GravityScale(0.5),
+            ))
+            .id();
+        world.spawn((Position(DVec2::new(1.5, -0.5)), PhysicsObject::at_rest(3.0)));
+        world.spawn((
+            Position(DVec2::ZERO),
+            PhysicsObject::at_rest(1.0),
+            GravityScale(0.0),
+        ));
+
+        world.run_system_once(apply_gravity).unwrap();
+        let physics_object = *world.get::<PhysicsObject>(body).unwrap();
+        let force = physics_object.mass * physics_object.acceleration;
+
+        let h = 1e-6;
+        for direction in [DVec2::X, DVec2::Y] {
+            world.get_mut::<Position>(body).unwrap().0 += h * direction;
+            let forward = potential_energy(&mut world);
+            world.get_mut::<Position>(body).unwrap().0 -= 2.0 * h * direction;
+            let backward = potential_energy(&mut world);
+            world.get_mut::<Position>(body).unwrap().0 += h * direction;
+
+            let gradient = (forward - backward) / (2.0 * h);
+            assert!((force.dot(direction) + gradient).abs() < 1e-6);
+        }
+    }
 }
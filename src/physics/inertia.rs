@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+
+use crate::components::{Density, PhysicsObject, Size};
+use crate::shapes::{Compound, Shape, ShapeImpl};
+
+/// Objects with a `Density` also get their mass from the area of their shape
+#[allow(clippy::type_complexity)]
+pub fn update_mass_properties(
+    mut query: Query<
+        (&Shape, &Size, Option<&Density>, &mut PhysicsObject),
+        Or<(
+            Added<PhysicsObject>,
+            Changed<Shape>,
+            Changed<Size>,
+            Changed<Density>,
+        )>,
+    >,
+) {
+    for (shape, size, density, mut physics_object) in &mut query {
+        let size = (*size).into();
+        if let Some(density) = density {
+            physics_object.mass = density.0 * shape.get_area(size);
+        }
+        let mass = physics_object.mass;
+        physics_object.moment_of_inertia = shape.get_moment_of_inertia(mass, size);
+    }
+}
+
+/// Compounds get both their mass and moment of inertia from their children
+#[allow(clippy::type_complexity)]
+pub fn update_compound_mass(
+    mut query: Query<
+        (&Compound, &Size, &mut PhysicsObject),
+        Or<(Added<PhysicsObject>, Changed<Compound>, Changed<Size>)>,
+    >,
+) {
+    for (compound, size, mut physics_object) in &mut query {
+        physics_object.mass = compound.mass();
+        physics_object.moment_of_inertia = compound.get_moment_of_inertia((*size).into());
+    }
+}
@@ -1,11 +1,17 @@
+use std::fmt;
+
 use bevy::ecs::system::ScheduleSystem;
 use bevy::math::DVec2;
 use bevy::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
 
-use crate::components::{PhysicsObject, Position};
+use super::step::{PhysicsSet, PhysicsStep};
+use crate::components::{BodyKind, PhysicsObject, Position, Rotation};
 
-/// Smallest allowable dt
-const DT_THRESHOLD: f64 = 1.0 / 30.0;
+/// Largest allowable dt
+pub(super) const DT_THRESHOLD: f64 = 1.0 / 30.0;
 
 macro_rules! check_dt_size {
     ($dt:ident, $physics_iter:expr) => {
@@ -13,105 +19,242 @@ macro_rules! check_dt_size {
             warn!("Ignoring a large step size equal to {}", $dt);
             for mut physics_object in $physics_iter {
                 physics_object.acceleration = DVec2::ZERO;
+                physics_object.angular_acceleration = 0.0;
             }
             return;
         }
     };
 }
 
+mod runge_kutta;
+
+use runge_kutta::{DormandPrinceStep, RungeKutta4Step, RungeKuttaForces};
+
+pub use runge_kutta::AdaptiveStepSize;
+
 pub trait Integrator {
-    fn build<F, M>(&self, app: &mut App, apply_forces: F)
+    /// `apply_forces` creates the force systems, which are added everywhere
+    /// the integrator needs the accelerations
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
     where
-        F: IntoScheduleConfigs<ScheduleSystem, M> + Copy;
+        F: IntoScheduleConfigs<ScheduleSystem, M>;
 }
 
-// TODO: Implement RK4
-#[allow(dead_code)]
+#[derive(
+    Resource,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    ValueEnum,
+    EnumIter,
+    Serialize,
+    Deserialize,
+)]
 pub enum Integrators {
     Euler,
     EulerChromer,
+    #[default]
     VelocityVerlet,
+    RungeKutta4,
+    DormandPrince,
+}
+
+impl fmt::Display for Integrators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Euler => f.write_str("Euler"),
+            Self::EulerChromer => f.write_str("Euler-Chromer"),
+            Self::VelocityVerlet => f.write_str("Velocity Verlet"),
+            Self::RungeKutta4 => f.write_str("RK4"),
+            Self::DormandPrince => f.write_str("Dormand-Prince"),
+        }
+    }
+}
+
+impl Integrators {
+    /// The integrator after this one, wrapping around at the end
+    pub fn next(self) -> Self {
+        Self::iter()
+            .cycle()
+            .skip_while(|integrator| *integrator != self)
+            .nth(1)
+            .expect("there should be more than one integrator")
+    }
+}
+
+/// Forces that are calculated once before the first step. This can be disabled
+/// when the accelerations are already known, like when restoring a snapshot.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InitialForces;
+
+/// The systems of an integrator, which only run when the integrator is selected
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct IntegratorSet(Integrators);
+
+/// Accelerations left over from the previous integrator should not
+/// be used by the next one. Objects added since the last step, like those
+/// restored from a snapshot, already have accelerations for the new integrator.
+pub fn reset_accelerations(integrator: Res<Integrators>, mut query: Query<&mut PhysicsObject>) {
+    if !integrator.is_changed() {
+        return;
+    }
+
+    for mut physics_object in &mut query {
+        if physics_object.is_added() {
+            continue;
+        }
+        physics_object.acceleration = DVec2::ZERO;
+        physics_object.angular_acceleration = 0.0;
+    }
+}
+
+/// Forces don't move static and kinematic objects, so their accelerations are
+/// removed before the integrators see them. Static objects also never move.
+pub fn ignore_forces(mut query: Query<&mut PhysicsObject>) {
+    for mut physics_object in &mut query {
+        match physics_object.kind {
+            BodyKind::Dynamic => continue,
+            BodyKind::Static => {
+                physics_object.velocity = DVec2::ZERO;
+                physics_object.angular_velocity = 0.0;
+            }
+            BodyKind::Kinematic => {}
+        }
+        physics_object.acceleration = DVec2::ZERO;
+        physics_object.angular_acceleration = 0.0;
+    }
 }
 
 impl Integrator for Integrators {
-    fn build<F, M>(&self, app: &mut App, apply_forces: F)
+    /// Build the integrator such that it only runs while it is the selected
+    /// `Integrators` resource. This lets several integrators be built at once.
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
     where
-        F: IntoScheduleConfigs<ScheduleSystem, M> + Copy,
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
     {
+        let set = IntegratorSet(*self);
+        app.configure_sets(PostStartup, set.run_if(resource_equals(*self)))
+            .configure_sets(
+                PhysicsStep,
+                set.run_if(resource_equals(*self))
+                    .in_set(PhysicsSet::Integrate),
+            )
+            .configure_sets(RungeKuttaForces, set.run_if(resource_equals(*self)));
+
         match self {
             Self::Euler => EulerStep.build(app, apply_forces),
             Self::EulerChromer => EulerChromerStep.build(app, apply_forces),
             Self::VelocityVerlet => VelocityVerletStep.build(app, apply_forces),
+            Self::RungeKutta4 => RungeKutta4Step.build(app, apply_forces),
+            Self::DormandPrince => DormandPrinceStep.build(app, apply_forces),
         };
     }
 }
 
 struct EulerStep;
 impl EulerStep {
-    fn step(timer: Res<Time>, mut query: Query<(&mut Position, &mut PhysicsObject)>) {
+    fn step(
+        timer: Res<Time>,
+        mut query: Query<(&mut Position, &mut Rotation, &mut PhysicsObject)>,
+    ) {
         let dt = timer.delta_secs_f64();
-        check_dt_size!(dt, query.iter_mut().map(|(_, p)| p));
+        check_dt_size!(dt, query.iter_mut().map(|(_, _, p)| p));
 
-        for (mut position, mut physics_object) in &mut query {
+        for (mut position, mut rotation, mut physics_object) in &mut query {
             let acceleration = physics_object.acceleration;
+            let angular_acceleration = physics_object.angular_acceleration;
             physics_object.acceleration = DVec2::ZERO;
+            physics_object.angular_acceleration = 0.0;
 
             position.0 += physics_object.velocity * dt;
             physics_object.velocity += acceleration * dt;
+
+            rotation.0 += physics_object.angular_velocity * dt;
+            physics_object.angular_velocity += angular_acceleration * dt;
         }
     }
 }
 
 impl Integrator for EulerStep {
-    fn build<F, M>(&self, app: &mut App, apply_forces: F)
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
     where
-        F: IntoScheduleConfigs<ScheduleSystem, M> + Copy,
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
     {
-        app.add_systems(FixedUpdate, (apply_forces, Self::step).chain());
+        app.add_systems(
+            PhysicsStep,
+            (apply_forces(), Self::step)
+                .chain()
+                .in_set(IntegratorSet(Integrators::Euler)),
+        );
     }
 }
 
 struct EulerChromerStep;
 impl EulerChromerStep {
-    fn step(timer: Res<Time>, mut query: Query<(&mut Position, &mut PhysicsObject)>) {
+    fn step(
+        timer: Res<Time>,
+        mut query: Query<(&mut Position, &mut Rotation, &mut PhysicsObject)>,
+    ) {
         let dt = timer.delta_secs_f64();
-        check_dt_size!(dt, query.iter_mut().map(|(_, p)| p));
+        check_dt_size!(dt, query.iter_mut().map(|(_, _, p)| p));
 
-        for (mut position, mut physics_object) in &mut query {
+        for (mut position, mut rotation, mut physics_object) in &mut query {
             let acceleration = physics_object.acceleration;
+            let angular_acceleration = physics_object.angular_acceleration;
             physics_object.acceleration = DVec2::ZERO;
+            physics_object.angular_acceleration = 0.0;
 
             physics_object.velocity += acceleration * dt;
             position.0 += physics_object.velocity * dt;
+
+            physics_object.angular_velocity += angular_acceleration * dt;
+            rotation.0 += physics_object.angular_velocity * dt;
         }
     }
 }
 
 impl Integrator for EulerChromerStep {
-    fn build<F, M>(&self, app: &mut App, apply_forces: F)
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
     where
-        F: IntoScheduleConfigs<ScheduleSystem, M> + Copy,
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
     {
-        app.add_systems(FixedUpdate, (apply_forces, Self::step).chain());
+        app.add_systems(
+            PhysicsStep,
+            (apply_forces(), Self::step)
+                .chain()
+                .in_set(IntegratorSet(Integrators::EulerChromer)),
+        );
     }
 }
 
 struct VelocityVerletStep;
 impl VelocityVerletStep {
     /// Runs before acceleration is calculated => uses previous acceleration
-    fn update_positions(timer: Res<Time>, mut query: Query<(&mut Position, &mut PhysicsObject)>) {
+    fn update_positions(
+        timer: Res<Time>,
+        mut query: Query<(&mut Position, &mut Rotation, &mut PhysicsObject)>,
+    ) {
         let dt = timer.delta_secs_f64();
         if dt > DT_THRESHOLD {
             return;
         }
 
-        for (mut position, mut physics_object) in &mut query {
+        for (mut position, mut rotation, mut physics_object) in &mut query {
             let acceleration = physics_object.acceleration;
+            let angular_acceleration = physics_object.angular_acceleration;
             physics_object.acceleration = DVec2::ZERO;
+            physics_object.angular_acceleration = 0.0;
 
             // half velocity step
             physics_object.velocity += 0.5 * acceleration * dt;
             position.0 += physics_object.velocity * dt;
+
+            physics_object.angular_velocity += 0.5 * angular_acceleration * dt;
+            rotation.0 += physics_object.angular_velocity * dt;
         }
     }
 
@@ -122,24 +265,185 @@ impl VelocityVerletStep {
 
         for mut physics_object in &mut query {
             let acceleration = physics_object.acceleration;
+            let angular_acceleration = physics_object.angular_acceleration;
             physics_object.velocity += 0.5 * acceleration * dt;
+            physics_object.angular_velocity += 0.5 * angular_acceleration * dt;
         }
     }
 }
 
 impl Integrator for VelocityVerletStep {
-    fn build<F, M>(&self, app: &mut App, apply_forces: F)
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
     where
-        F: IntoScheduleConfigs<ScheduleSystem, M> + Copy,
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
     {
-        app.add_systems(PostStartup, apply_forces).add_systems(
-            FixedUpdate,
+        let set = IntegratorSet(Integrators::VelocityVerlet);
+        app.add_systems(
+            PostStartup,
+            apply_forces().in_set(set).in_set(InitialForces),
+        )
+        .add_systems(
+            PhysicsStep,
             (
                 Self::update_positions,
-                apply_forces,
+                apply_forces(),
                 Self::update_velocities,
             )
-                .chain(),
+                .chain()
+                .in_set(set),
         );
     }
 }
+
+impl Integrator for RungeKutta4Step {
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
+    where
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
+    {
+        let set = IntegratorSet(Integrators::RungeKutta4);
+        app.add_systems(RungeKuttaForces, apply_forces().in_set(set))
+            .add_systems(PhysicsStep, Self::step.in_set(set));
+    }
+}
+
+impl Integrator for DormandPrinceStep {
+    fn build<F, M>(&self, app: &mut App, apply_forces: fn() -> F)
+    where
+        F: IntoScheduleConfigs<ScheduleSystem, M>,
+    {
+        let set = IntegratorSet(Integrators::DormandPrince);
+        app.init_resource::<AdaptiveStepSize>()
+            .add_systems(RungeKuttaForces, apply_forces().in_set(set))
+            .add_systems(PhysicsStep, Self::step.in_set(set));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Connection, Size, SpringForce, Tangible};
+    use crate::physics::{run_steps, test_app};
+    use crate::shapes::Shape;
+
+    const STEPS: usize = 120;
+
+    /// A tangible 4×1 platform of the given kind
+    fn spawn_platform(
+        app: &mut App,
+        kind: BodyKind,
+        velocity: DVec2,
+        angular_velocity: f64,
+    ) -> Entity {
+        app.world_mut()
+            .spawn((
+                Shape::Square,
+                Position(DVec2::ZERO),
+                Size {
+                    width: 4.0,
+                    height: 1.0,
+                },
+                PhysicsObject {
+                    velocity,
+                    angular_velocity,
+                    kind,
+                    ..PhysicsObject::at_rest(1.0)
+                },
+                Tangible,
+            ))
+            .id()
+    }
+
+    /// A block that falls onto the platform, and a weight hanging from the
+    /// platform by a spring. Returns the block.
+    fn spawn_neighbours(app: &mut App, platform: Entity) -> Entity {
+        let mut spawn_square = |position| {
+            app.world_mut()
+                .spawn((
+                    Shape::Square,
+                    Position(position),
+                    Size::default(),
+                    PhysicsObject::at_rest(1.0),
+                    Tangible,
+                ))
+                .id()
+        };
+        let block = spawn_square(DVec2::new(0.0, 1.5));
+        let weight = spawn_square(DVec2::new(1.5, -2.0));
+        app.world_mut().spawn((
+            SpringForce {
+                spring_constant: 50.0,
+                ..default()
+            },
+            Connection {
+                entity1: platform,
+                entity2: weight,
+            },
+        ));
+        block
+    }
+
+    fn state(app: &App, entity: Entity) -> (DVec2, f64, PhysicsObject) {
+        let world = app.world();
+        (
+            world.get::<Position>(entity).unwrap().0,
+            world.get::<Rotation>(entity).unwrap().0,
+            *world.get::<PhysicsObject>(entity).unwrap(),
+        )
+    }
+
+    /// Static objects stay exactly where they are under gravity, springs and
+    /// collisions, even when they were given a velocity
+    #[test]
+    fn test_static_objects_never_move() {
+        for integrator in Integrators::iter() {
+            let mut app = test_app(integrator);
+            let platform = spawn_platform(&mut app, BodyKind::Static, DVec2::ONE, 1.0);
+            let block = spawn_neighbours(&mut app, platform);
+
+            for _ in 0..STEPS {
+                run_steps(&mut app, 1);
+                let (position, rotation, physics_object) = state(&app, platform);
+                assert_eq!((position, rotation), (DVec2::ZERO, 0.0), "{integrator}");
+                assert_eq!(physics_object.velocity, DVec2::ZERO, "{integrator}");
+            }
+            // the block landed on the platform instead of falling through
+            assert!(state(&app, block).0.y > 0.5, "{integrator}");
+        }
+    }
+
+    /// Kinematic objects keep their velocity under gravity, springs and
+    /// collisions, so they move exactly as scripted
+    #[test]
+    fn test_kinematic_objects_keep_their_velocity() {
+        let velocity = DVec2::new(0.5, 0.0);
+        let angular_velocity = 0.2;
+        for integrator in Integrators::iter() {
+            let mut app = test_app(integrator);
+            let platform =
+                spawn_platform(&mut app, BodyKind::Kinematic, velocity, angular_velocity);
+            let block = spawn_neighbours(&mut app, platform);
+
+            let timestep = app
+                .world()
+                .resource::<Time<Fixed>>()
+                .timestep()
+                .as_secs_f64();
+            for step in 1..=STEPS {
+                run_steps(&mut app, 1);
+                let time = step as f64 * timestep;
+                let (position, rotation, physics_object) = state(&app, platform);
+                assert!(position.distance(velocity * time) < 1e-9, "{integrator}");
+                assert!(
+                    (rotation - angular_velocity * time).abs() < 1e-9,
+                    "{integrator}"
+                );
+                assert_eq!(physics_object.velocity, velocity, "{integrator}");
+                assert_eq!(
+                    physics_object.angular_velocity, angular_velocity,
+                    "{integrator}"
+                );
+            }
+            assert!(state(&app, block).0.y > 0.5, "{integrator}");
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::components::{BodyKind, PhysicsObject, Position, Rotation};
+
+use super::DT_THRESHOLD;
+
+/// Schedule containing the force systems, so they can be evaluated at every
+/// stage of a Runge-Kutta step.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RungeKuttaForces;
+
+/// Absolute error tolerance of adaptive methods
+const ABSOLUTE_TOLERANCE: f64 = 1e-8;
+/// Relative error tolerance of adaptive methods
+const RELATIVE_TOLERANCE: f64 = 1e-6;
+/// Smallest step an adaptive method is allowed to take
+const MIN_STEP_SIZE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BodyState {
+    pub position: DVec2,
+    pub velocity: DVec2,
+    pub rotation: f64,
+    pub angular_velocity: f64,
+}
+
+/// The time derivative of a `BodyState`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BodyDerivative {
+    velocity: DVec2,
+    acceleration: DVec2,
+    angular_velocity: f64,
+    angular_acceleration: f64,
+}
+
+impl BodyDerivative {
+    pub const fn new(state: &BodyState, acceleration: DVec2, angular_acceleration: f64) -> Self {
+        Self {
+            velocity: state.velocity,
+            acceleration,
+            angular_velocity: state.angular_velocity,
+            angular_acceleration,
+        }
+    }
+}
+
+impl BodyState {
+    fn add_scaled(&mut self, derivative: &BodyDerivative, scale: f64) {
+        self.position += derivative.velocity * scale;
+        self.velocity += derivative.acceleration * scale;
+        self.rotation += derivative.angular_velocity * scale;
+        self.angular_velocity += derivative.angular_acceleration * scale;
+    }
+
+    /// Largest difference between two states relative to the error tolerance.
+    /// States that have blown up have an infinite error, so the step is
+    /// rejected instead of `f64::max` dropping the NaN.
+    fn scaled_error(&self, other: &Self, reference: &Self) -> f64 {
+        let scaled = |a: f64, b: f64, reference: f64| {
+            let error = (a - b).abs()
+                / (ABSOLUTE_TOLERANCE + RELATIVE_TOLERANCE * a.abs().max(reference.abs()));
+            if error.is_nan() { f64::INFINITY } else { error }
+        };
+
+        [
+            scaled(self.position.x, other.position.x, reference.position.x),
+            scaled(self.position.y, other.position.y, reference.position.y),
+            scaled(self.velocity.x, other.velocity.x, reference.velocity.x),
+            scaled(self.velocity.y, other.velocity.y, reference.velocity.y),
+            scaled(self.rotation, other.rotation, reference.rotation),
+            scaled(
+                self.angular_velocity,
+                other.angular_velocity,
+                reference.angular_velocity,
+            ),
+        ]
+        .into_iter()
+        .fold(0.0, f64::max)
+    }
+}
+
+/// The coefficients of an explicit Runge-Kutta method with `S` stages.
+pub struct ButcherTableau<const S: usize> {
+    a: [[f64; S]; S],
+    b: [f64; S],
+    /// An embedded solution of lower order, used to estimate the error
+    embedded: Option<EmbeddedSolution<S>>,
+}
+
+struct EmbeddedSolution<const S: usize> {
+    b: [f64; S],
+    /// Used to pick the next step size
+    order: i32,
+}
+
+pub const RK4: ButcherTableau<4> = ButcherTableau {
+    a: [
+        [0.0, 0.0, 0.0, 0.0],
+        [0.5, 0.0, 0.0, 0.0],
+        [0.0, 0.5, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ],
+    b: [1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+    embedded: None,
+};
+
+pub const DORMAND_PRINCE: ButcherTableau<7> = ButcherTableau {
+    a: [
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0, 0.0],
+        [
+            19372.0 / 6561.0,
+            -25360.0 / 2187.0,
+            64448.0 / 6561.0,
+            -212.0 / 729.0,
+            0.0,
+            0.0,
+            0.0,
+        ],
+        [
+            9017.0 / 3168.0,
+            -355.0 / 33.0,
+            46732.0 / 5247.0,
+            49.0 / 176.0,
+            -5103.0 / 18656.0,
+            0.0,
+            0.0,
+        ],
+        [
+            35.0 / 384.0,
+            0.0,
+            500.0 / 1113.0,
+            125.0 / 192.0,
+            -2187.0 / 6784.0,
+            11.0 / 84.0,
+            0.0,
+        ],
+    ],
+    b: [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+        0.0,
+    ],
+    embedded: Some(EmbeddedSolution {
+        b: [
+            5179.0 / 57600.0,
+            0.0,
+            7571.0 / 16695.0,
+            393.0 / 640.0,
+            -92097.0 / 339200.0,
+            187.0 / 2100.0,
+            1.0 / 40.0,
+        ],
+        order: 4,
+    }),
+};
+
+impl<const S: usize> ButcherTableau<S> {
+    /// Take a single step of size `h`. Returns the new states, and, if the
+    /// method has an embedded solution, the error relative to the tolerance.
+    pub fn step(
+        &self,
+        states: &[BodyState],
+        h: f64,
+        mut derivative: impl FnMut(&[BodyState]) -> Vec<BodyDerivative>,
+    ) -> (Vec<BodyState>, Option<f64>) {
+        let mut stages: Vec<Vec<BodyDerivative>> = Vec::with_capacity(S);
+        for i in 0..S {
+            let mut stage_states = states.to_vec();
+            for (j, stage) in stages.iter().enumerate() {
+                if self.a[i][j] == 0.0 {
+                    continue;
+                }
+                for (state, derivative) in stage_states.iter_mut().zip(stage) {
+                    state.add_scaled(derivative, h * self.a[i][j]);
+                }
+            }
+            stages.push(derivative(&stage_states));
+        }
+
+        let combine = |weights: &[f64; S]| {
+            let mut new_states = states.to_vec();
+            for (weight, stage) in weights.iter().zip(&stages) {
+                for (state, derivative) in new_states.iter_mut().zip(stage) {
+                    state.add_scaled(derivative, h * weight);
+                }
+            }
+            new_states
+        };
+
+        let new_states = combine(&self.b);
+        let error = self.embedded.as_ref().map(|embedded| {
+            combine(&embedded.b)
+                .iter()
+                .zip(&new_states)
+                .zip(states)
+                .map(|((embedded, new), old)| new.scaled_error(embedded, old))
+                .fold(0.0, f64::max)
+        });
+
+        (new_states, error)
+    }
+
+    /// Take as many steps as needed to advance the states by `dt`, adjusting
+    /// the step size so the error stays within the tolerance. `step_size` is the
+    /// step size to start with, and is updated to the next suggested step size.
+    pub fn adaptive_step(
+        &self,
+        states: &[BodyState],
+        dt: f64,
+        step_size: &mut f64,
+        mut derivative: impl FnMut(&[BodyState]) -> Vec<BodyDerivative>,
+    ) -> Vec<BodyState> {
+        const SAFETY_FACTOR: f64 = 0.9;
+
+        let mut states = states.to_vec();
+        let mut t = 0.0;
+        while t < dt {
+            let h = step_size.max(MIN_STEP_SIZE).min(dt - t);
+            let (new_states, error) = self.step(&states, h, &mut derivative);
+            let error = error.unwrap_or(0.0);
+
+            // error ∝ hᵖ⁺¹, so this is the h that would give an error equal to the tolerance
+            let factor = self.embedded.as_ref().map_or(f64::INFINITY, |embedded| {
+                SAFETY_FACTOR * error.powf(-1.0 / f64::from(embedded.order + 1))
+            });
+            let factor = if factor.is_finite() {
+                factor.clamp(0.2, 5.0)
+            } else {
+                5.0
+            };
+
+            if error <= 1.0 || h <= MIN_STEP_SIZE {
+                states = new_states;
+                t += h;
+            }
+            *step_size = h * factor;
+        }
+
+        states
+    }
+}
+
+fn get_states(world: &mut World) -> (Vec<Entity>, Vec<BodyState>) {
+    let mut query = world.query::<(Entity, &Position, &Rotation, &mut PhysicsObject)>();
+    query
+        .iter_mut(world)
+        .map(|(entity, position, rotation, mut physics_object)| {
+            // remove any leftover accelerations so they are not counted twice
+            physics_object.acceleration = DVec2::ZERO;
+            physics_object.angular_acceleration = 0.0;
+
+            // the stages move the objects before `ignore_forces` sees them
+            if physics_object.kind == BodyKind::Static {
+                physics_object.velocity = DVec2::ZERO;
+                physics_object.angular_velocity = 0.0;
+            }
+
+            let state = BodyState {
+                position: position.0,
+                velocity: physics_object.velocity,
+                rotation: rotation.0,
+                angular_velocity: physics_object.angular_velocity,
+            };
+            (entity, state)
+        })
+        .unzip()
+}
+
+fn set_states(world: &mut World, entities: &[Entity], states: &[BodyState]) {
+    let mut query = world.query::<(&mut Position, &mut Rotation, &mut PhysicsObject)>();
+    for (entity, state) in entities.iter().zip(states) {
+        let Ok((mut position, mut rotation, mut physics_object)) = query.get_mut(world, *entity)
+        else {
+            continue;
+        };
+        position.0 = state.position;
+        rotation.0 = state.rotation;
+        physics_object.velocity = state.velocity;
+        physics_object.angular_velocity = state.angular_velocity;
+    }
+}
+
+/// Calculate the derivatives of the given states by running the force systems.
+fn evaluate_forces(
+    world: &mut World,
+    entities: &[Entity],
+    states: &[BodyState],
+) -> Vec<BodyDerivative> {
+    set_states(world, entities, states);
+    world.run_schedule(RungeKuttaForces);
+
+    let mut query = world.query::<&mut PhysicsObject>();
+    entities
+        .iter()
+        .zip(states)
+        .map(|(entity, state)| {
+            let Ok(mut physics_object) = query.get_mut(world, *entity) else {
+                return BodyDerivative::new(state, DVec2::ZERO, 0.0);
+            };
+            let acceleration = physics_object.acceleration;
+            let angular_acceleration = physics_object.angular_acceleration;
+            physics_object.acceleration = DVec2::ZERO;
+            physics_object.angular_acceleration = 0.0;
+
+            BodyDerivative::new(state, acceleration, angular_acceleration)
+        })
+        .collect()
+}
+
+pub struct RungeKutta4Step;
+impl RungeKutta4Step {
+    pub fn step(world: &mut World) {
+        let dt = world.resource::<Time>().delta_secs_f64();
+        check_dt_size!(dt, world.query::<&mut PhysicsObject>().iter_mut(world));
+
+        let (entities, states) = get_states(world);
+        let (new_states, _) = RK4.step(&states, dt, |states| {
+            evaluate_forces(world, &entities, states)
+        });
+        set_states(world, &entities, &new_states);
+    }
+}
+
+/// The step size the adaptive integrator will try next. This is zero until
+/// the first step, where it starts out as the fixed time step.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct AdaptiveStepSize(pub f64);
+
+pub struct DormandPrinceStep;
+impl DormandPrinceStep {
+    pub fn step(world: &mut World) {
+        let dt = world.resource::<Time>().delta_secs_f64();
+        check_dt_size!(dt, world.query::<&mut PhysicsObject>().iter_mut(world));
+
+        let mut step_size = world.resource::<AdaptiveStepSize>().0;
+        if step_size <= 0.0 {
+            step_size = dt;
+        }
+
+        let (entities, states) = get_states(world);
+        let new_states = DORMAND_PRINCE.adaptive_step(&states, dt, &mut step_size, |states| {
+            evaluate_forces(world, &entities, states)
+        });
+        set_states(world, &entities, &new_states);
+        world.resource_mut::<AdaptiveStepSize>().0 = step_size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A harmonic oscillator with ω = 1, so x(t) = cos(t)
+    fn harmonic_oscillator(states: &[BodyState]) -> Vec<BodyDerivative> {
+        states
+            .iter()
+            .map(|state| BodyDerivative::new(state, -state.position, 0.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_rk4() {
+        let dt = 0.1;
+        let mut states = vec![BodyState {
+            position: DVec2::X,
+            ..Default::default()
+        }];
+
+        for _ in 0..100 {
+            (states, _) = RK4.step(&states, dt, harmonic_oscillator);
+        }
+
+        let t: f64 = 100.0 * dt;
+        assert!((states[0].position.x - t.cos()).abs() < 1e-5);
+        assert!((states[0].velocity.x + t.sin()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_dormand_prince() {
+        let dt = 0.1;
+        let mut step_size = dt;
+        let mut states = vec![BodyState {
+            position: DVec2::X,
+            ..Default::default()
+        }];
+
+        for _ in 0..100 {
+            states = DORMAND_PRINCE.adaptive_step(&states, dt, &mut step_size, harmonic_oscillator);
+        }
+
+        let t: f64 = 100.0 * dt;
+        assert!((states[0].position.x - t.cos()).abs() < 1e-6);
+        assert!((states[0].velocity.x + t.sin()).abs() < 1e-6);
+    }
+
+    /// A step that is far too big blows up, and must be rejected rather than
+    /// accepted with no error
+    #[test]
+    fn test_dormand_prince_rejects_blow_up() {
+        // the oscillator never goes further than 1, but the force is NaN
+        // beyond 2, where the stages of a big step end up
+        let blowing_up = |states: &[BodyState]| {
+            states
+                .iter()
+                .map(|state| {
+                    let x = state.position.x;
+                    let acceleration = if x.abs() > 2.0 { f64::NAN } else { -x };
+                    BodyDerivative::new(state, DVec2::X * acceleration, 0.0)
+                })
+                .collect()
+        };
+
+        let dt = 10.0;
+        let mut step_size = dt;
+        let states = vec![BodyState {
+            position: DVec2::X,
+            ..Default::default()
+        }];
+        let (new_states, error) = DORMAND_PRINCE.step(&states, dt, blowing_up);
+        assert!(new_states[0].position.is_nan());
+        assert_eq!(error, Some(f64::INFINITY));
+
+        let states = DORMAND_PRINCE.adaptive_step(&states, dt, &mut step_size, blowing_up);
+        assert!((states[0].position.x - dt.cos()).abs() < 1e-5);
+        assert!(step_size < dt);
+    }
+}
@@ -0,0 +1,576 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::components::{
+    Connection, Joint, JointKind, PhysicsObject, Position, Rotation, Size, Spring,
+};
+
+/// Gauss–Seidel iterations over every joint. More iterations make long chains
+/// stiffer.
+const SOLVER_ITERATIONS: usize = 32;
+
+type JointBodyQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Position,
+        Option<&'static mut Rotation>,
+        Option<&'static mut PhysicsObject>,
+    ),
+    (Without<Joint>, Without<Spring>),
+>;
+
+/// The state of a connected entity while the joints are solved. Entities
+/// without a `PhysicsObject`, and static and kinematic ones, have no inverse
+/// mass, so joints can't move them.
+#[derive(Debug, Clone, Copy)]
+struct Body {
+    position: DVec2,
+    rotation: f64,
+    velocity: DVec2,
+    angular_velocity: f64,
+    acceleration: DVec2,
+    angular_acceleration: f64,
+    inverse_mass: f64,
+    inverse_inertia: f64,
+}
+
+impl Body {
+    fn read(query: &JointBodyQuery, entity: Entity) -> Option<Self> {
+        let (position, rotation, physics_object) = query.get(entity).ok()?;
+        let mut body = Self {
+            position: position.0,
+            rotation: rotation.map_or(0.0, |rotation| rotation.0),
+            velocity: DVec2::ZERO,
+            angular_velocity: 0.0,
+            acceleration: DVec2::ZERO,
+            angular_acceleration: 0.0,
+            inverse_mass: 0.0,
+            inverse_inertia: 0.0,
+        };
+        if let Some(physics_object) = physics_object {
+            body.velocity = physics_object.velocity;
+            body.angular_velocity = physics_object.angular_velocity;
+            body.acceleration = physics_object.acceleration;
+            body.angular_acceleration = physics_object.angular_acceleration;
+            body.inverse_mass = physics_object.inverse_mass();
+            body.inverse_inertia = physics_object.inverse_moment_of_inertia();
+        }
+        Some(body)
+    }
+
+    fn write(&self, query: &mut JointBodyQuery, entity: Entity) {
+        let Ok((mut position, rotation, physics_object)) = query.get_mut(entity) else {
+            return;
+        };
+        let Some(mut physics_object) = physics_object else {
+            return;
+        };
+        position.0 = self.position;
+        if let Some(mut rotation) = rotation {
+            rotation.0 = self.rotation;
+        }
+        physics_object.velocity = self.velocity;
+        physics_object.angular_velocity = self.angular_velocity;
+        physics_object.acceleration = self.acceleration;
+        physics_object.angular_acceleration = self.angular_acceleration;
+    }
+
+    const fn velocities(&self) -> (DVec2, f64) {
+        (self.velocity, self.angular_velocity)
+    }
+
+    const fn accelerations(&self) -> (DVec2, f64) {
+        (self.acceleration, self.angular_acceleration)
+    }
+
+    /// Move the body along a row of the constraint Jacobian
+    fn apply(&mut self, level: Level, linear: DVec2, angular: f64, lambda: f64) {
+        let linear = self.inverse_mass * lambda * linear;
+        let angular = self.inverse_inertia * lambda * angular;
+        match level {
+            Level::Position => {
+                self.position += linear;
+                self.rotation += angular;
+            }
+            Level::Velocity => {
+                self.velocity += linear;
+                self.angular_velocity += angular;
+            }
+            Level::Acceleration => {
+                self.acceleration += linear;
+                self.angular_acceleration += angular;
+            }
+        }
+    }
+}
+
+/// Which part of the state a joint is enforced on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Position,
+    Velocity,
+    Acceleration,
+}
+
+/// A single scalar condition of a joint
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    /// The anchors line up along a fixed direction
+    Point(DVec2),
+    /// The anchors are a fixed distance apart
+    Distance(f64),
+    /// The second anchor stays on the line through the first one, at this
+    /// angle to the first entity
+    Slide(f64),
+    /// The rotation of the second entity relative to the first is fixed
+    Angle(f64),
+}
+
+impl Joint {
+    fn conditions(&self) -> impl Iterator<Item = Condition> {
+        let angle = Condition::Angle(self.reference_angle);
+        let conditions = match self.kind {
+            JointKind::Distance { length } => [Some(Condition::Distance(length)), None, None],
+            JointKind::Revolute => [
+                Some(Condition::Point(DVec2::X)),
+                Some(Condition::Point(DVec2::Y)),
+                None,
+            ],
+            JointKind::Prismatic { axis } => [Some(Condition::Slide(axis)), Some(angle), None],
+            JointKind::Weld => [
+                Some(Condition::Point(DVec2::X)),
+                Some(Condition::Point(DVec2::Y)),
+                Some(angle),
+            ],
+        };
+        conditions.into_iter().flatten()
+    }
+}
+
+/// A row of the constraint Jacobian, which weighs the velocities of both
+/// bodies, and how far its condition is from being met
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    linear1: DVec2,
+    angular1: f64,
+    linear2: DVec2,
+    angular2: f64,
+    error: f64,
+}
+
+impl Row {
+    fn new(
+        level: Level,
+        condition: Condition,
+        joint: &Joint,
+        body1: &Body,
+        body2: &Body,
+    ) -> Option<Self> {
+        let offset1 = DVec2::from_angle(body1.rotation).rotate(joint.anchor1);
+        let offset2 = DVec2::from_angle(body2.rotation).rotate(joint.anchor2);
+        let between = body2.position + offset2 - body1.position - offset1;
+        let relative_velocity = body2.velocity + body2.angular_velocity * offset2.perp()
+            - body1.velocity
+            - body1.angular_velocity * offset1.perp();
+        // relative acceleration of the anchors caused by the bodies spinning
+        let centripetal =
+            body1.angular_velocity.powi(2) * offset1 - body2.angular_velocity.powi(2) * offset2;
+
+        // `bias` is the part of the acceleration error that doesn't depend on
+        // the accelerations of the bodies
+        let point_row = |direction: DVec2, position_error: f64, bias: f64| Self {
+            linear1: -direction,
+            angular1: -offset1.perp_dot(direction),
+            linear2: direction,
+            angular2: offset2.perp_dot(direction),
+            error: if level == Level::Position {
+                position_error
+            } else {
+                bias
+            },
+        };
+
+        let mut row = match condition {
+            Condition::Point(direction) => point_row(
+                direction,
+                between.dot(direction),
+                direction.dot(centripetal),
+            ),
+            Condition::Distance(length) => {
+                let direction = between.try_normalize()?;
+                let along = relative_velocity.dot(direction);
+                let across = relative_velocity.length_squared() - along * along;
+                point_row(
+                    direction,
+                    between.length() - length,
+                    direction.dot(centripetal) + across / between.length(),
+                )
+            }
+            Condition::Slide(axis) => {
+                let direction = DVec2::from_angle(body1.rotation + axis).perp();
+                let turning = body1.angular_velocity;
+                let mut row = point_row(
+                    direction,
+                    between.dot(direction),
+                    direction.dot(centripetal)
+                        + 2.0 * turning * direction.perp().dot(relative_velocity)
+                        - turning * turning * direction.dot(between),
+                );
+                // the line turns along with the first body
+                row.angular1 += between.dot(direction.perp());
+                row
+            }
+            Condition::Angle(reference_angle) => Self {
+                linear1: DVec2::ZERO,
+                angular1: -1.0,
+                linear2: DVec2::ZERO,
+                angular2: 1.0,
+                error: if level == Level::Position {
+                    body2.rotation - body1.rotation - reference_angle
+                } else {
+                    0.0
+                },
+            },
+        };
+
+        match level {
+            Level::Position => {}
+            Level::Velocity => row.error = row.dot(body1.velocities(), body2.velocities()),
+            Level::Acceleration => {
+                row.error += row.dot(body1.accelerations(), body2.accelerations());
+            }
+        }
+        Some(row)
+    }
+
+    fn dot(&self, (linear1, angular1): (DVec2, f64), (linear2, angular2): (DVec2, f64)) -> f64 {
+        self.linear1.dot(linear1)
+            + self.angular1 * angular1
+            + self.linear2.dot(linear2)
+            + self.angular2 * angular2
+    }
+
+    /// Change the bodies along the row so that the error becomes zero
+    fn solve(&self, level: Level, body1: &mut Body, body2: &mut Body) {
+        let inverse_mass = body1.inverse_mass * self.linear1.length_squared()
+            + body1.inverse_inertia * self.angular1 * self.angular1
+            + body2.inverse_mass * self.linear2.length_squared()
+            + body2.inverse_inertia * self.angular2 * self.angular2;
+        if inverse_mass == 0.0 {
+            return;
+        }
+
+        let lambda = -self.error / inverse_mass;
+        body1.apply(level, self.linear1, self.angular1, lambda);
+        body2.apply(level, self.linear2, self.angular2, lambda);
+    }
+}
+
+fn solve(
+    level: Level,
+    joint_query: &Query<(&Joint, &Connection)>,
+    body_query: &mut JointBodyQuery,
+) {
+    for _ in 0..SOLVER_ITERATIONS {
+        for (joint, connection) in joint_query {
+            if connection.entity1 == connection.entity2 {
+                continue;
+            }
+            let (Some(mut body1), Some(mut body2)) = (
+                Body::read(body_query, connection.entity1),
+                Body::read(body_query, connection.entity2),
+            ) else {
+                continue;
+            };
+
+            for condition in joint.conditions() {
+                if let Some(row) = Row::new(level, condition, joint, &body1, &body2) {
+                    row.solve(level, &mut body1, &mut body2);
+                }
+            }
+            body1.write(body_query, connection.entity1);
+            body2.write(body_query, connection.entity2);
+        }
+    }
+}
+
+/// Add the forces that hold the joints together. This runs after every other
+/// force, as it cancels the parts of them that would pull the joints apart.
+pub fn apply_joint_forces(
+    joint_query: Query<(&Joint, &Connection)>,
+    mut body_query: JointBodyQuery,
+) {
+    solve(Level::Acceleration, &joint_query, &mut body_query);
+}
+
+/// Project the bodies back onto their joints. The joint forces only keep them
+/// there up to the error of the integrator, which would otherwise build up.
+pub fn solve_joints(joint_query: Query<(&Joint, &Connection)>, mut body_query: JointBodyQuery) {
+    solve(Level::Position, &joint_query, &mut body_query);
+    solve(Level::Velocity, &joint_query, &mut body_query);
+}
+
+/// Lay out joints as bars between the entities they connect. Distance joints
+/// are drawn between their anchors, and joints that pin their anchors together
+/// between the centers of the entities.
+pub fn update_joint(
+    mut commands: Commands,
+    mut joint_query: Query<(
+        Entity,
+        &Joint,
+        &Connection,
+        &mut Position,
+        &mut Size,
+        &mut Rotation,
+    )>,
+    body_query: Query<(&Position, Option<&Rotation>), Without<Joint>>,
+) {
+    for (entity, joint, connection, mut position, mut size, mut rotation) in &mut joint_query {
+        let (Ok(body1), Ok(body2)) = (
+            body_query.get(connection.entity1),
+            body_query.get(connection.entity2),
+        ) else {
+            debug!("Despawning joint with invalid connections");
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let body1 = (body1.0.0, body1.1.map_or(0.0, |rotation| rotation.0));
+        let body2 = (body2.0.0, body2.1.map_or(0.0, |rotation| rotation.0));
+        let (pos1, pos2) = match joint.kind {
+            JointKind::Distance { .. } => joint.anchor_positions(body1, body2),
+            _ => (body1.0, body2.0),
+        };
+
+        let between = pos1 - pos2;
+        position.0 = (pos1 + pos2) / 2.0;
+        rotation.0 = DVec2::angle_to(DVec2::X, between);
+        size.width = between.length();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::components::BodyKind;
+    use crate::physics::{Gravity, Integrators, run_steps, test_app};
+    use crate::shapes::Shape;
+
+    const STEPS: usize = 300;
+    /// How far a joint may be from its condition after each step
+    const TOLERANCE: f64 = 1e-5;
+
+    fn joint(kind: JointKind, anchor2: DVec2, reference_angle: f64) -> Joint {
+        Joint {
+            kind,
+            anchor1: DVec2::ZERO,
+            anchor2,
+            reference_angle,
+        }
+    }
+
+    /// A 1×0.2 bar, which can turn
+    fn spawn_bar(app: &mut App, position: DVec2, rotation: f64) -> Entity {
+        app.world_mut()
+            .spawn((
+                Shape::Square,
+                Position(position),
+                Rotation(rotation),
+                Size {
+                    width: 1.0,
+                    height: 0.2,
+                },
+                PhysicsObject::at_rest(1.0),
+            ))
+            .id()
+    }
+
+    fn spawn_joint(app: &mut App, joint: Joint, entity1: Entity, entity2: Entity) {
+        app.world_mut()
+            .spawn((joint, Connection { entity1, entity2 }));
+    }
+
+    fn spawn_static(app: &mut App) -> Entity {
+        app.world_mut()
+            .spawn((
+                Position(DVec2::ZERO),
+                PhysicsObject {
+                    kind: BodyKind::Static,
+                    ..PhysicsObject::at_rest(1.0)
+                },
+            ))
+            .id()
+    }
+
+    fn position(app: &App, entity: Entity) -> DVec2 {
+        app.world().get::<Position>(entity).unwrap().0
+    }
+
+    fn rotation(app: &App, entity: Entity) -> f64 {
+        app.world().get::<Rotation>(entity).unwrap().0
+    }
+
+    /// Where the left end of a bar is
+    fn left_end(app: &App, bar: Entity) -> DVec2 {
+        position(app, bar) + DVec2::from_angle(rotation(app, bar)).rotate(DVec2::new(-0.5, 0.0))
+    }
+
+    /// With every integrator, check that `error` stays within the tolerance
+    /// while the last of the bodies moves, and that the first one, which is
+    /// static or a bare anchor at the origin, never moves
+    fn check_joint(
+        setup: impl Fn(&mut App) -> Vec<Entity>,
+        error: impl Fn(&App, &[Entity]) -> f64,
+    ) {
+        for integrator in Integrators::iter() {
+            let mut app = test_app(integrator);
+            let entities = setup(&mut app);
+            let moving = *entities.last().unwrap();
+            let start = position(&app, moving);
+            let mut furthest: f64 = 0.0;
+
+            for _ in 0..STEPS {
+                run_steps(&mut app, 1);
+                let error = error(&app, &entities);
+                assert!(error < TOLERANCE, "{integrator}: {error}");
+                furthest = furthest.max(position(&app, moving).distance(start));
+            }
+            assert_eq!(position(&app, entities[0]), DVec2::ZERO, "{integrator}");
+            assert!(furthest > 0.1, "{integrator}");
+        }
+    }
+
+    #[test]
+    fn test_distance_pendulum() {
+        for integrator in Integrators::iter() {
+            let mut app = test_app(integrator);
+            let pivot = app.world_mut().spawn(Position(DVec2::ZERO)).id();
+            let bob = app
+                .world_mut()
+                .spawn((Position(DVec2::X), PhysicsObject::at_rest(1.0)))
+                .id();
+            spawn_joint(
+                &mut app,
+                joint(JointKind::Distance { length: 1.0 }, DVec2::ZERO, 0.0),
+                pivot,
+                bob,
+            );
+
+            let gravity = app.world().resource::<Gravity>().0;
+            let energy = |app: &App| {
+                let physics_object = app.world().get::<PhysicsObject>(bob).unwrap();
+                physics_object.kinetic_energy()
+                    - physics_object.mass * gravity.dot(position(app, bob))
+            };
+            let initial_energy = energy(&app);
+
+            for _ in 0..STEPS {
+                run_steps(&mut app, 1);
+                let length_error = (position(&app, bob).length() - 1.0).abs();
+                assert!(length_error < TOLERANCE, "{integrator}: {length_error}");
+                // the first order methods turn the velocity of the bob with an
+                // explicit force, which gains energy every step
+                if !matches!(integrator, Integrators::Euler | Integrators::EulerChromer) {
+                    let energy_error = (energy(&app) - initial_energy).abs();
+                    assert!(
+                        energy_error < 0.01 * gravity.length(),
+                        "{integrator}: {energy_error}"
+                    );
+                }
+            }
+            assert_eq!(position(&app, pivot), DVec2::ZERO, "{integrator}");
+        }
+    }
+
+    #[test]
+    fn test_revolute() {
+        // hanging from its left end, so it swings
+        check_joint(
+            |app| {
+                let pivot = spawn_static(app);
+                let bar = spawn_bar(app, DVec2::new(0.5, 0.0), 0.0);
+                spawn_joint(
+                    app,
+                    joint(JointKind::Revolute, DVec2::new(-0.5, 0.0), 0.0),
+                    pivot,
+                    bar,
+                );
+                vec![pivot, bar]
+            },
+            |app, entities| left_end(app, entities[1]).length(),
+        );
+    }
+
+    #[test]
+    fn test_prismatic() {
+        // sliding down a slope, while something tries to turn it
+        let axis = -0.5;
+        check_joint(
+            |app| {
+                let base = spawn_static(app);
+                let bar = spawn_bar(app, DVec2::from_angle(axis) + DVec2::new(0.5, 0.0), 0.0);
+                app.world_mut()
+                    .get_mut::<PhysicsObject>(bar)
+                    .unwrap()
+                    .angular_velocity = 2.0;
+                spawn_joint(
+                    app,
+                    joint(JointKind::Prismatic { axis }, DVec2::new(-0.5, 0.0), 0.0),
+                    base,
+                    bar,
+                );
+                vec![base, bar]
+            },
+            |app, entities| {
+                let bar = entities[1];
+                let off_axis = left_end(app, bar).dot(DVec2::from_angle(axis).perp());
+                off_axis.abs().max(rotation(app, bar).abs())
+            },
+        );
+    }
+
+    #[test]
+    fn test_weld() {
+        // two bars welded at an angle, swinging from a hinge
+        let reference_angle = 0.3;
+        check_joint(
+            |app| {
+                let pivot = app.world_mut().spawn(Position(DVec2::ZERO)).id();
+                let bar1 = spawn_bar(app, DVec2::new(0.5, 0.0), 0.0);
+                spawn_joint(
+                    app,
+                    joint(JointKind::Revolute, DVec2::new(-0.5, 0.0), 0.0),
+                    pivot,
+                    bar1,
+                );
+                let bar2 = spawn_bar(
+                    app,
+                    DVec2::X + DVec2::from_angle(reference_angle) * 0.5,
+                    reference_angle,
+                );
+                spawn_joint(
+                    app,
+                    Joint {
+                        kind: JointKind::Weld,
+                        anchor1: DVec2::new(0.5, 0.0),
+                        anchor2: DVec2::new(-0.5, 0.0),
+                        reference_angle,
+                    },
+                    bar1,
+                    bar2,
+                );
+                vec![pivot, bar1, bar2]
+            },
+            |app, entities| {
+                let (bar1, bar2) = (entities[1], entities[2]);
+                let angle = rotation(app, bar2) - rotation(app, bar1) - reference_angle;
+                let right_end = position(app, bar1)
+                    + DVec2::from_angle(rotation(app, bar1)).rotate(DVec2::new(0.5, 0.0));
+                angle.abs().max(right_end.distance(left_end(app, bar2)))
+            },
+        );
+    }
+}
@@ -1,30 +1,135 @@
+mod boundary;
 mod collision;
+mod dissipation;
+mod drag;
 mod energy;
 mod gravity;
+mod inertia;
 mod integrators;
+mod joint;
 mod spring;
+mod state;
+mod step;
 mod transform;
 
+use bevy::ecs::schedule::ScheduleConfigs;
+use bevy::ecs::system::ScheduleSystem;
 use bevy::prelude::*;
+use strum::IntoEnumIterator;
 
-use collision::apply_collision_force;
+use boundary::{update_boundary_walls, wrap_around};
+use collision::resolve_collisions;
+use dissipation::{measure_dissipated_power, measure_kinetic_energy, track_dissipated_energy};
+use drag::apply_drag;
 use energy::calculate_total_energy;
 use gravity::apply_gravity;
-use integrators::{Integrator, Integrators};
-use spring::{apply_spring_force, update_spring};
-use transform::update_transform;
+use inertia::{update_compound_mass, update_mass_properties};
+use integrators::{Integrator, ignore_forces, reset_accelerations};
+use joint::{apply_joint_forces, solve_joints, update_joint};
+use spring::{apply_spring_force, break_springs, update_spring};
+use state::write_state;
+use step::{PhysicsSet, PhysicsStep, setup_physics_step};
+
+pub use boundary::{BoundaryKind, BoundaryWall, WorldBoundary};
+pub use dissipation::DissipatedEnergy;
+pub use drag::{AirResistance, Drag};
+pub use gravity::{Attractor, Gravity, GravityScale, MutualGravity};
+pub use integrators::{AdaptiveStepSize, InitialForces, Integrators};
+pub use step::SubSteps;
+pub use transform::update_transform;
 
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        Integrators::VelocityVerlet.build(
-            app,
-            (apply_gravity, apply_spring_force, apply_collision_force),
-        );
+        app.init_resource::<Integrators>()
+            .init_resource::<Gravity>()
+            .init_resource::<SubSteps>()
+            .init_resource::<AirResistance>()
+            .init_resource::<DissipatedEnergy>()
+            .init_resource::<WorldBoundary>();
+        setup_physics_step(app);
+        for integrator in Integrators::iter() {
+            integrator.build(app, apply_forces);
+        }
         app.add_systems(
-            Update,
-            (calculate_total_energy, update_transform, update_spring),
+            FixedPreUpdate,
+            (
+                update_mass_properties,
+                update_compound_mass,
+                reset_accelerations,
+                update_boundary_walls.run_if(resource_changed::<WorldBoundary>),
+            ),
+        )
+        .add_systems(
+            PhysicsStep,
+            (
+                measure_dissipated_power.before(PhysicsSet::Integrate),
+                solve_joints.in_set(PhysicsSet::SolveConstraints),
+                measure_kinetic_energy
+                    .after(PhysicsSet::SolveConstraints)
+                    .before(PhysicsSet::ResolveCollisions),
+                resolve_collisions.in_set(PhysicsSet::ResolveCollisions),
+                (track_dissipated_energy, break_springs)
+                    .chain()
+                    .after(PhysicsSet::ResolveCollisions),
+                wrap_around.after(PhysicsSet::ResolveCollisions),
+            ),
         );
+        // once per step, including the steps run while paused
+        app.add_systems(FixedPostUpdate, (calculate_total_energy, write_state))
+            .add_systems(Update, (update_spring, update_joint));
+    }
+}
+
+/// Every force on the physics objects. The joint forces cancel whatever would
+/// pull the joints apart, so they have to come last, once the forces on
+/// static and kinematic objects have been removed.
+fn apply_forces() -> ScheduleConfigs<ScheduleSystem> {
+    (
+        (apply_gravity, apply_spring_force, apply_drag),
+        ignore_forces,
+        apply_joint_forces,
+    )
+        .chain()
+}
+
+/// An app that runs nothing but the physics, one fixed step of 1/60 s per
+/// update, for testing the physics systems together
+#[cfg(test)]
+pub(crate) fn test_app(integrator: Integrators) -> App {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    let timestep = Duration::from_secs_f64(1.0 / 60.0);
+    let mut app = App::new();
+    app.add_plugins(TimePlugin)
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<ColorMaterial>>()
+        .init_resource::<crate::Energy>()
+        .init_resource::<crate::EnergyFile>()
+        .init_resource::<crate::StateFile>()
+        .insert_resource(Time::<Fixed>::from_duration(timestep))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+        .insert_resource(integrator)
+        .add_plugins(PhysicsPlugin);
+    app
+}
+
+/// Run `steps` fixed steps of an app made by `test_app`. The very first
+/// update has no delta, so it only runs the startup systems.
+#[cfg(test)]
+pub(crate) fn run_steps(app: &mut App, steps: usize) {
+    if app
+        .world()
+        .resource::<Time<Real>>()
+        .first_update()
+        .is_none()
+    {
+        app.update();
+    }
+    for _ in 0..steps {
+        app.update();
     }
 }
@@ -1,6 +1,7 @@
 use bevy::math::DVec2;
 use bevy::prelude::*;
 
+use super::DissipatedEnergy;
 use crate::components::{Connection, PhysicsObject, Position, Rotation, Size, Spring, SpringForce};
 
 fn get_spring_connection_positions(
@@ -17,6 +18,29 @@ fn get_spring_connection_positions(
     Some((pos1, pos2))
 }
 
+/// Length of the spring minus its equilibrium length, and the direction from
+/// the first end to the second. The direction is zero when the ends are at
+/// the same point, like when the mouse grabs an object at its center.
+fn spring_displacement(spring_force: &SpringForce, pos1: DVec2, pos2: DVec2) -> (f64, DVec2) {
+    let between = pos2 - pos1;
+    (
+        between.length() - spring_force.equilibrium_length,
+        between.normalize_or_zero(),
+    )
+}
+
+/// How fast the ends of a spring move apart along the spring. Ends without a
+/// `PhysicsObject` don't move.
+fn extension_rate(
+    connection: &Connection,
+    direction: DVec2,
+    velocity_of: impl Fn(Entity) -> Option<DVec2>,
+) -> f64 {
+    let velocity1 = velocity_of(connection.entity1).unwrap_or_default();
+    let velocity2 = velocity_of(connection.entity2).unwrap_or_default();
+    (velocity2 - velocity1).dot(direction)
+}
+
 pub fn apply_spring_force(
     spring_query: Query<(&SpringForce, &Connection)>,
     position_query: Query<&Position, Without<Spring>>,
@@ -28,25 +52,51 @@ pub fn apply_spring_force(
             continue;
         };
 
-        let between = pos2 - pos1;
-        let length = between.length();
-        let direction = between / length;
+        let (displacement, direction) = spring_displacement(spring_force, pos1, pos2);
+        if !spring_force.is_engaged(displacement) {
+            continue;
+        }
 
-        let displacement = length - spring_force.equilibrium_length;
-        let force = spring_force.spring_constant * direction * displacement;
+        // only stretching and compressing is damped, so the spring can still
+        // swing and move freely as a whole
+        let extension_rate = extension_rate(connection, direction, |entity| {
+            physics_query
+                .get(entity)
+                .ok()
+                .map(|physics| physics.velocity)
+        });
+        let force =
+            (spring_force.force(displacement) + spring_force.damping * extension_rate) * direction;
 
-        for entity in [connection.entity1, connection.entity2] {
+        for (entity, force) in [(connection.entity1, force), (connection.entity2, -force)] {
             let Ok(mut physics) = physics_query.get_mut(entity) else {
                 continue;
             };
             let mass = physics.mass;
-            let damping = spring_force.damping * physics.velocity;
+            physics.acceleration += force / mass;
+        }
+    }
+}
 
-            if entity == connection.entity1 {
-                physics.acceleration += (force - damping) / mass;
-            } else {
-                physics.acceleration += (-force - damping) / mass;
-            }
+/// Remove springs that have been stretched or compressed past their breaking
+/// point. The energy they stored is lost.
+pub fn break_springs(
+    mut commands: Commands,
+    spring_query: Query<(Entity, &SpringForce, &Connection)>,
+    position_query: Query<&Position, Without<Spring>>,
+    mut dissipated_energy: ResMut<DissipatedEnergy>,
+) {
+    for (entity, spring_force, connection) in &spring_query {
+        let Some((pos1, pos2)) = get_spring_connection_positions(connection, &position_query)
+        else {
+            continue;
+        };
+
+        let (displacement, _) = spring_displacement(spring_force, pos1, pos2);
+        if spring_force.is_broken(displacement) {
+            debug!("Breaking an overstretched spring");
+            dissipated_energy.total += spring_force.potential_energy(displacement);
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -88,8 +138,173 @@ pub fn spring_potential_energy(
             continue;
         };
 
-        let elongation = (pos2 - pos1).length() - spring_force.equilibrium_length;
-        total_energy += 0.5 * spring_force.spring_constant * elongation.powi(2);
+        let (displacement, _) = spring_displacement(spring_force, pos1, pos2);
+        total_energy += spring_force.potential_energy(displacement);
     }
     total_energy
 }
+
+/// The rate at which spring damping is removing energy
+pub fn spring_damping_power(
+    spring_query: &Query<(&SpringForce, &Connection)>,
+    position_query: &Query<&Position, Without<Spring>>,
+    velocity_of: impl Fn(Entity) -> Option<DVec2> + Copy,
+) -> f64 {
+    let mut power = 0.0;
+    for (spring_force, connection) in spring_query {
+        let Some((pos1, pos2)) = get_spring_connection_positions(connection, position_query) else {
+            continue;
+        };
+
+        let (displacement, direction) = spring_displacement(spring_force, pos1, pos2);
+        if spring_force.is_engaged(displacement) {
+            power +=
+                spring_force.damping * extension_rate(connection, direction, velocity_of).powi(2);
+        }
+    }
+    power
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::components::SpringLaw;
+
+    fn spring_force(law: SpringLaw) -> SpringForce {
+        SpringForce {
+            damping: 5.0,
+            spring_constant: 4.0,
+            equilibrium_length: 1.0,
+            law,
+            max_strain: None,
+        }
+    }
+
+    /// The force should be the gradient of the potential energy, otherwise
+    /// the energy readout drifts even when the integrator is exact
+    #[test]
+    fn test_force_matches_potential_energy() {
+        let h = 1e-6;
+        for law in [
+            SpringLaw::Linear,
+            SpringLaw::Cubic { coefficient: 3.0 },
+            SpringLaw::CompressionOnly,
+            SpringLaw::TensionOnly,
+        ] {
+            let spring_force = spring_force(law);
+            for displacement in [-0.7, -0.2, 0.3, 0.9] {
+                let gradient = (spring_force.potential_energy(displacement + h)
+                    - spring_force.potential_energy(displacement - h))
+                    / (2.0 * h);
+                assert!(
+                    (spring_force.force(displacement) - gradient).abs() < 1e-6,
+                    "{law:?} at {displacement}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_damping_when_moving_together() {
+        let mut world = World::new();
+        let velocity = DVec2::new(1.0, 2.0);
+        let mut spawn_end = |position| {
+            world
+                .spawn((
+                    Position(position),
+                    PhysicsObject {
+                        velocity,
+                        ..PhysicsObject::at_rest(1.0)
+                    },
+                ))
+                .id()
+        };
+        let entity1 = spawn_end(DVec2::ZERO);
+        let entity2 = spawn_end(DVec2::new(0.6, 0.8));
+        world.spawn((
+            spring_force(SpringLaw::Linear),
+            Connection { entity1, entity2 },
+        ));
+
+        // at its equilibrium length, so any force would come from damping
+        world.run_system_once(apply_spring_force).unwrap();
+        for entity in [entity1, entity2] {
+            let acceleration = world.get::<PhysicsObject>(entity).unwrap().acceleration;
+            assert!(acceleration.length() < 1e-12);
+        }
+
+        world.get_mut::<PhysicsObject>(entity2).unwrap().velocity = DVec2::ZERO;
+        world.run_system_once(apply_spring_force).unwrap();
+        let acceleration = world.get::<PhysicsObject>(entity2).unwrap().acceleration;
+        assert!(acceleration.length() > 1.0);
+    }
+
+    /// A spring whose ends meet, like one the mouse attaches at the center of
+    /// an object, has no direction to push in
+    #[test]
+    fn test_ends_at_the_same_point() {
+        let mut world = World::new();
+        let end = |velocity| {
+            (
+                Position(DVec2::ONE),
+                PhysicsObject {
+                    velocity,
+                    ..PhysicsObject::at_rest(1.0)
+                },
+            )
+        };
+        let entity1 = world.spawn(end(DVec2::X)).id();
+        let entity2 = world.spawn(end(DVec2::ZERO)).id();
+        world.spawn((
+            spring_force(SpringLaw::Linear),
+            Connection { entity1, entity2 },
+        ));
+
+        world.run_system_once(apply_spring_force).unwrap();
+        for entity in [entity1, entity2] {
+            let acceleration = world.get::<PhysicsObject>(entity).unwrap().acceleration;
+            assert_eq!(acceleration, DVec2::ZERO);
+        }
+
+        let power = world
+            .run_system_once(
+                |spring_query: Query<(&SpringForce, &Connection)>,
+                 position_query: Query<&Position, Without<Spring>>,
+                 physics_query: Query<&PhysicsObject>| {
+                    spring_damping_power(&spring_query, &position_query, |entity| {
+                        physics_query
+                            .get(entity)
+                            .ok()
+                            .map(|physics| physics.velocity)
+                    })
+                },
+            )
+            .unwrap();
+        assert_eq!(power, 0.0);
+    }
+
+    #[test]
+    fn test_broken_spring_dissipates_its_energy() {
+        let mut world = World::new();
+        world.init_resource::<DissipatedEnergy>();
+        let entity1 = world.spawn(Position(DVec2::ZERO)).id();
+        let entity2 = world.spawn(Position(DVec2::new(2.0, 0.0))).id();
+        let spring_force = SpringForce {
+            max_strain: Some(0.5),
+            ..spring_force(SpringLaw::Linear)
+        };
+        let spring = world
+            .spawn((spring_force, Connection { entity1, entity2 }))
+            .id();
+
+        world.run_system_once(break_springs).unwrap();
+        assert!(world.get_entity(spring).is_err());
+        assert_eq!(
+            world.resource::<DissipatedEnergy>().total,
+            spring_force.potential_energy(1.0)
+        );
+        assert_eq!(spring_force.potential_energy(1.0), 2.0);
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use crate::StateFile;
+use crate::components::{PhysicsObject, Position, Rotation};
+
+use std::{fs::OpenOptions, io::Write};
+
+/// Append the position, rotation and velocities of every physics object
+/// to the state file, one line per object.
+pub fn write_state(
+    timer: Res<Time>,
+    state_file_resource: Res<StateFile>,
+    query: Query<(Entity, &Position, &Rotation, &PhysicsObject)>,
+) {
+    let Some(filename) = &state_file_resource.0 else {
+        return;
+    };
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(filename)
+        .expect("state file should exist");
+
+    let mut bodies: Vec<_> = query.iter().collect();
+    bodies.sort_unstable_by_key(|(entity, ..)| *entity);
+
+    for (entity, position, rotation, physics_object) in bodies {
+        writeln!(
+            file,
+            "{:.15} {} {:.15} {:.15} {:.15} {:.15} {:.15} {:.15}",
+            timer.elapsed_secs_f64(),
+            entity.index(),
+            position.0.x,
+            position.0.y,
+            rotation.0,
+            physics_object.velocity.x,
+            physics_object.velocity.y,
+            physics_object.angular_velocity,
+        )
+        .expect("state file should exist");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::math::DVec2;
+    use bevy::time::TimeUpdateStrategy;
+
+    use crate::physics::{Integrators, run_steps, test_app};
+
+    use super::*;
+
+    #[test]
+    fn test_one_row_per_step() {
+        let path = std::env::temp_dir().join(format!("state-{}.txt", std::process::id()));
+        fs::write(&path, "").unwrap();
+
+        let mut app = test_app(Integrators::default());
+        // every frame runs two fixed steps
+        let timestep = app.world().resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(2 * timestep))
+            .insert_resource(crate::StateFile(Some(path.clone().into())));
+        for x in [0.0, 2.0] {
+            app.world_mut()
+                .spawn((Position(DVec2::new(x, 0.0)), PhysicsObject::at_rest(1.0)));
+        }
+        run_steps(&mut app, 5);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let times: Vec<f64> = contents
+            .lines()
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(times.len(), 2 * 10);
+        for (step, rows) in times.chunks(2).enumerate() {
+            let time = (step + 1) as f64 * timestep.as_secs_f64();
+            assert!(
+                rows.iter().all(|row| (row - time).abs() < 1e-9),
+                "{rows:?} at {time}"
+            );
+        }
+    }
+}
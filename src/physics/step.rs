@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::integrators::DT_THRESHOLD;
+
+/// A single physics step: integration, then the joint constraints, then
+/// collision resolution. It runs `SubSteps` times per fixed step, with `Time`
+/// set to the sub-step.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhysicsStep;
+
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhysicsSet {
+    Integrate,
+    SolveConstraints,
+    ResolveCollisions,
+}
+
+/// The number of physics steps per fixed step. Stiff springs need small steps
+/// to stay stable, and sub-stepping gives them that without running every
+/// other fixed system more often.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubSteps(pub u32);
+
+impl Default for SubSteps {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl SubSteps {
+    /// The largest number of sub-steps that can be selected
+    pub const MAX: u32 = 64;
+
+    /// Whether a fixed step of `timestep` split into this many sub-steps is
+    /// small enough for the integrators
+    pub fn is_stable(self, timestep: Duration) -> bool {
+        self.0 >= 1 && self.0 <= Self::MAX && (timestep / self.0).as_secs_f64() <= DT_THRESHOLD
+    }
+
+    /// The number of sub-steps after this one, wrapping back to one
+    pub fn next(self) -> Self {
+        if self.0 >= Self::MAX {
+            Self(1)
+        } else {
+            Self(self.0 * 2)
+        }
+    }
+
+    /// The fewest sub-steps that keep a fixed step of `timestep` stable
+    pub fn minimum(timestep: Duration) -> Self {
+        Self((timestep.as_secs_f64() / DT_THRESHOLD).ceil().max(1.0) as u32)
+    }
+}
+
+pub(super) fn setup_physics_step(app: &mut App) {
+    app.init_schedule(PhysicsStep)
+        .configure_sets(
+            PhysicsStep,
+            (
+                PhysicsSet::Integrate,
+                PhysicsSet::SolveConstraints,
+                PhysicsSet::ResolveCollisions,
+            )
+                .chain(),
+        )
+        .add_systems(FixedUpdate, run_sub_steps);
+}
+
+/// Split the fixed step into equal sub-steps. `Time` is advanced by one
+/// sub-step at a time so the integrators only ever see the sub-step.
+fn run_sub_steps(world: &mut World) {
+    let sub_steps = world.resource::<SubSteps>().0.max(1);
+    let fixed_time = *world.resource::<Time>();
+    let dt = fixed_time.delta() / sub_steps;
+
+    let mut time = Time::<()>::default();
+    time.advance_to(fixed_time.elapsed() - fixed_time.delta());
+    for _ in 0..sub_steps {
+        time.advance_by(dt);
+        *world.resource_mut::<Time>() = time;
+        world.run_schedule(PhysicsStep);
+    }
+    *world.resource_mut::<Time>() = fixed_time;
+}
@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{error, fmt, fs, io};
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::mouse::{create_mouse_spring, destroy_mouse_spring, move_mouse_spring};
+use crate::physics::{DissipatedEnergy, Integrators, SubSteps, WorldBoundary};
+use crate::scenes::GameScene;
+use crate::snapshot::{LoadedSnapshot, Snapshot, take_snapshot};
+
+/// Anything from outside the simulation that changes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SimulationInput {
+    GrabWithMouse([f64; 2]),
+    MoveMouse([f64; 2]),
+    ReleaseMouse,
+    SwitchScene(GameScene),
+    LoadSnapshot(Snapshot),
+    SetIntegrator(Integrators),
+    SetTimestep(Duration),
+    SetSubSteps(SubSteps),
+    SetBoundary(WorldBoundary),
+}
+
+/// Inputs that will be applied at the start of the next fixed step. Applying
+/// inputs at fixed steps instead of whenever they happen is what makes it
+/// possible to replay them.
+#[derive(Resource, Default)]
+pub struct InputQueue(Vec<SimulationInput>);
+
+impl InputQueue {
+    /// Queue an input. Only the last mouse movement before a step matters,
+    /// so consecutive movements replace each other.
+    pub fn push(&mut self, input: SimulationInput) {
+        if let (SimulationInput::MoveMouse(_), Some(SimulationInput::MoveMouse(_))) =
+            (&input, self.0.last())
+        {
+            self.0.pop();
+        }
+        self.0.push(input);
+    }
+}
+
+/// The number of fixed steps that have been completed
+#[derive(Resource, Default)]
+pub struct SimulationTick(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// The step the input was applied before
+    pub tick: u64,
+    pub input: SimulationInput,
+}
+
+/// The initial state of a run, along with every input that was applied to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub initial: Snapshot,
+    /// The number of steps in the run
+    pub ticks: u64,
+    pub inputs: Vec<RecordedInput>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Deserialize(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access replay file: {err}"),
+            Self::Deserialize(err) => write!(f, "invalid replay file: {err}"),
+            Self::Serialize(err) => write!(f, "failed to serialize replay: {err}"),
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let contents = fs::read_to_string(path).map_err(ReplayError::Io)?;
+        ron::from_str(&contents).map_err(ReplayError::Deserialize)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(ReplayError::Serialize)?;
+        fs::write(path, contents).map_err(ReplayError::Io)
+    }
+}
+
+/// Record the run, and save it to `path` when the app exits
+#[derive(Resource)]
+pub struct Recording {
+    pub path: PathBuf,
+    replay: Option<Replay>,
+}
+
+impl Recording {
+    pub const fn new(path: PathBuf) -> Self {
+        Self { path, replay: None }
+    }
+}
+
+/// Feed the inputs of a replay back into the simulation. Live inputs are
+/// ignored until the replay is over.
+#[derive(Resource)]
+pub struct Replaying {
+    ticks: u64,
+    inputs: VecDeque<RecordedInput>,
+}
+
+impl Replaying {
+    /// Start replaying. The initial state must be loaded separately, by
+    /// starting in `GameScene::Snapshot` with `replay.initial` loaded.
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            ticks: replay.ticks,
+            inputs: replay.inputs.into(),
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputQueue>()
+            .init_resource::<SimulationTick>()
+            .add_systems(
+                FixedFirst,
+                (
+                    start_recording.run_if(resource_exists::<Recording>),
+                    feed_replay.run_if(resource_exists::<Replaying>),
+                    apply_inputs,
+                )
+                    .chain(),
+            )
+            .add_systems(FixedLast, count_ticks)
+            .add_systems(
+                Last,
+                save_recording
+                    .run_if(resource_exists::<Recording>)
+                    .run_if(on_event::<AppExit>),
+            );
+    }
+}
+
+fn count_ticks(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+fn start_recording(world: &mut World) {
+    if world.resource::<Recording>().replay.is_some() {
+        return;
+    }
+
+    let initial = world
+        .run_system_cached(take_snapshot)
+        .expect("snapshot system should be valid");
+    world.resource_mut::<Recording>().replay = Some(Replay {
+        initial,
+        ticks: 0,
+        inputs: Vec::new(),
+    });
+}
+
+fn feed_replay(
+    tick: Res<SimulationTick>,
+    mut replaying: ResMut<Replaying>,
+    mut queue: ResMut<InputQueue>,
+) {
+    if tick.0 >= replaying.ticks {
+        return;
+    }
+
+    queue.0.clear();
+    while replaying
+        .inputs
+        .front()
+        .is_some_and(|recorded| recorded.tick == tick.0)
+    {
+        let recorded = replaying.inputs.pop_front().expect("input should exist");
+        queue.0.push(recorded.input);
+    }
+}
+
+fn apply_inputs(world: &mut World) {
+    let inputs = std::mem::take(&mut world.resource_mut::<InputQueue>().0);
+    if inputs.is_empty() {
+        return;
+    }
+
+    for input in &inputs {
+        match input {
+            SimulationInput::GrabWithMouse(position) => world
+                .run_system_cached_with(create_mouse_spring, DVec2::from(*position))
+                .expect("mouse system should be valid"),
+            SimulationInput::MoveMouse(position) => world
+                .run_system_cached_with(move_mouse_spring, DVec2::from(*position))
+                .expect("mouse system should be valid"),
+            SimulationInput::ReleaseMouse => world
+                .run_system_cached(destroy_mouse_spring)
+                .expect("mouse system should be valid"),
+            SimulationInput::SwitchScene(scene) => switch_scene(world, *scene),
+            SimulationInput::LoadSnapshot(snapshot) => {
+                world.insert_resource(LoadedSnapshot(snapshot.clone()));
+                switch_scene(world, GameScene::Snapshot);
+            }
+            SimulationInput::SetIntegrator(integrator) => world.insert_resource(*integrator),
+            SimulationInput::SetTimestep(timestep) => {
+                world.resource_mut::<Time<Fixed>>().set_timestep(*timestep)
+            }
+            SimulationInput::SetSubSteps(sub_steps) => world.insert_resource(*sub_steps),
+            SimulationInput::SetBoundary(boundary) => world.insert_resource(*boundary),
+        }
+    }
+
+    let tick = world.resource::<SimulationTick>().0;
+    if let Some(mut recording) = world.get_resource_mut::<Recording>()
+        && let Some(replay) = &mut recording.replay
+    {
+        replay.inputs.extend(
+            inputs
+                .into_iter()
+                .map(|input| RecordedInput { tick, input }),
+        );
+    }
+}
+
+/// Switch scene right away, so the new scene is ready for the next step
+fn switch_scene(world: &mut World, scene: GameScene) {
+    // a restored snapshot brings back its own dissipated energy when entered
+    world.insert_resource(DissipatedEnergy::default());
+    world.resource_mut::<NextState<GameScene>>().set(scene);
+    world.run_schedule(StateTransition);
+}
+
+fn save_recording(tick: Res<SimulationTick>, mut recording: ResMut<Recording>) {
+    let Some(mut replay) = recording.replay.take() else {
+        return;
+    };
+    replay.ticks = tick.0;
+
+    match replay.save(&recording.path) {
+        Ok(()) => info!("Saved replay to {}", recording.path.display()),
+        Err(err) => error!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::{BodyKind, PhysicsObject, Position};
+    use crate::headless::HeadlessPlugin;
+    use crate::physics::PhysicsPlugin;
+    use crate::snapshot::{SnapshotFile, SnapshotPlugin};
+
+    use super::*;
+
+    fn headless_app(scene: GameScene, setup: impl FnOnce(&mut App)) -> App {
+        let timestep = Duration::from_secs_f64(1.0 / 60.0);
+        let mut app = App::new();
+        app.add_plugins(HeadlessPlugin {
+            scene,
+            steps: u64::MAX,
+            timestep,
+        })
+        .init_resource::<crate::Energy>()
+        .init_resource::<crate::EnergyFile>()
+        .init_resource::<crate::StateFile>()
+        .insert_resource(Time::<Fixed>::from_duration(timestep))
+        .insert_resource(SnapshotFile {
+            path: PathBuf::new(),
+            save_on_exit: false,
+        })
+        .add_plugins((PhysicsPlugin, SnapshotPlugin, ReplayPlugin));
+        setup(&mut app);
+        // the first update has no delta, so it only sets up the scene
+        app.update();
+        app
+    }
+
+    /// Where a body that can be grabbed is
+    fn dynamic_body_position(app: &mut App) -> [f64; 2] {
+        let world = app.world_mut();
+        let mut query = world.query::<(&Position, &PhysicsObject)>();
+        query
+            .iter(world)
+            .find(|(_, physics_object)| physics_object.kind == BodyKind::Dynamic)
+            .map(|(position, _)| position.0.to_array())
+            .expect("the scene should have a dynamic body")
+    }
+
+    fn step_with(app: &mut App, input: Option<SimulationInput>) {
+        if let Some(input) = input {
+            app.world_mut().resource_mut::<InputQueue>().push(input);
+        }
+        app.update();
+    }
+
+    fn take(app: &mut App) -> Snapshot {
+        app.world_mut()
+            .run_system_cached(take_snapshot)
+            .expect("snapshot system should be valid")
+    }
+
+    /// Replaying a run, which starts from a snapshot of the live scene,
+    /// should end in exactly the same state
+    #[test]
+    fn test_replay_is_deterministic() {
+        let mut app = headless_app(GameScene::BouncyCastle, |app| {
+            app.insert_resource(Recording::new(PathBuf::new()));
+        });
+
+        for _ in 0..10 {
+            step_with(&mut app, None);
+        }
+        for scene in [GameScene::BouncyCastle, GameScene::SpringPendulum] {
+            if scene != GameScene::BouncyCastle {
+                step_with(&mut app, Some(SimulationInput::SwitchScene(scene)));
+            }
+            let [x, y] = dynamic_body_position(&mut app);
+            step_with(&mut app, Some(SimulationInput::GrabWithMouse([x, y])));
+            for i in 0..30 {
+                let offset = 0.02 * f64::from(i);
+                step_with(
+                    &mut app,
+                    Some(SimulationInput::MoveMouse([x + offset, y - offset])),
+                );
+            }
+            step_with(&mut app, Some(SimulationInput::ReleaseMouse));
+            for _ in 0..30 {
+                step_with(&mut app, None);
+            }
+        }
+
+        let recorded = take(&mut app);
+        let mut replay = app
+            .world_mut()
+            .resource_mut::<Recording>()
+            .replay
+            .take()
+            .expect("the run should be recorded");
+        replay.ticks = app.world().resource::<SimulationTick>().0;
+        assert!(replay.inputs.len() > 60);
+
+        let ticks = replay.ticks;
+        let mut app = headless_app(GameScene::Snapshot, |app| {
+            app.insert_resource(LoadedSnapshot(replay.initial.clone()))
+                .insert_resource(Replaying::new(replay));
+        });
+        for _ in 0..ticks {
+            app.update();
+        }
+
+        assert_eq!(app.world().resource::<SimulationTick>().0, ticks);
+        assert_eq!(take(&mut app).without_ids(), recorded.without_ids());
+    }
+}
@@ -1,13 +1,11 @@
-use crate::components::Position;
-use crate::shapes::{Shape, SpringShape};
-
-use bevy::math::DVec2;
 use bevy::prelude::*;
 
+use super::file::SceneDescription;
 use super::{GameScene, despawn_scene};
-use crate::spawners::{Spawner, spring::spring_bundle, square::physics_square_bundle};
 
-#[derive(Component)]
+pub const BOUNCY_CASTLE: &str = include_str!("../../assets/scenes/bouncy_castle.ron");
+
+#[derive(Component, Clone)]
 struct BouncyCastleEntity;
 
 pub struct BouncyCastlePlugin;
@@ -22,44 +20,6 @@ impl Plugin for BouncyCastlePlugin {
     }
 }
 
-fn spawn_square(
-    position: DVec2,
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-) -> Entity {
-    Spawner::new(BouncyCastleEntity, commands)
-        .with_bundle(physics_square_bundle(0.1, 0.5, 0.5, position))
-        .with_shape(Shape::Square, meshes)
-        .with_color(Color::srgb_u8(150, 50, 100), materials)
-        .id()
-}
-
-fn spawn_spring(
-    entity1: Entity,
-    entity2: Entity,
-    strength: f64,
-    length: f64,
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-) -> Entity {
-    Spawner::new(BouncyCastleEntity, commands)
-        .with_bundle(spring_bundle(
-            0.1, entity1, entity2, 0.001, strength, length,
-        ))
-        .with_shape(
-            Shape::Spring(SpringShape {
-                coil_count: 20,
-                coil_diameter: 0.01,
-            }),
-            meshes,
-        )
-        .with_color(Color::srgb_u8(0, 100, 200), materials)
-        .with_z_value(-1.0)
-        .id()
-}
-
 pub fn bouncy_castle_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -67,126 +27,12 @@ pub fn bouncy_castle_setup(
 ) {
     debug!("Setting up bouncy castle");
 
-    let fixed_point = commands
-        .spawn((BouncyCastleEntity, Position(DVec2::new(0.0, 2.5))))
-        .id();
-
-    let top_square = spawn_square(
-        DVec2::new(0.0, 1.5),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        fixed_point,
-        top_square,
-        80.0,
-        1.0,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    let left_square_top = spawn_square(
-        DVec2::new(-1.0, 0.5),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        top_square,
-        left_square_top,
-        20.0,
-        2f64.sqrt(),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    let right_square_top = spawn_square(
-        DVec2::new(1.0, 0.5),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        top_square,
-        right_square_top,
-        20.0,
-        2f64.sqrt(),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    spawn_spring(
-        right_square_top,
-        left_square_top,
-        10.0,
-        2.0,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    let left_square_bottom = spawn_square(
-        DVec2::new(-1.0, -0.5),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        left_square_top,
-        left_square_bottom,
-        10.0,
-        1.0,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    let right_square_bottom = spawn_square(
-        DVec2::new(1.0, -0.5),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        right_square_top,
-        right_square_bottom,
-        10.0,
-        1.0,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    spawn_spring(
-        right_square_bottom,
-        left_square_bottom,
-        10.0,
-        2.0,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-
-    spawn_spring(
-        right_square_top,
-        left_square_bottom,
-        10.0,
-        5f64.sqrt(),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
-    spawn_spring(
-        left_square_top,
-        right_square_bottom,
-        10.0,
-        5f64.sqrt(),
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
+    SceneDescription::from_ron(BOUNCY_CASTLE)
+        .expect("bouncy castle scene should be valid")
+        .spawn(
+            BouncyCastleEntity,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
 }
@@ -4,7 +4,8 @@ use std::iter::zip;
 use crate::components::{Position, Rotation, Size, Tangible};
 use crate::debug::bounding_box::BoundingBoxColor;
 use crate::mouse::get_clicked_entity;
-use crate::shapes::{Shape, ShapeImpl};
+use crate::shapes::{CollisionData, Shape, ShapeImpl};
+use crate::utils::sweep_and_prune;
 use crate::{MousePosition, WindowSize};
 
 use bevy::input::common_conditions::{input_just_pressed, input_just_released, input_pressed};
@@ -65,20 +66,41 @@ fn highlight_colliding(
 ) {
     let mut collisions: HashMap<_, Vec<_>> = HashMap::new();
 
-    for (entity1, shape1, position1, size1, rotation1, _) in &query {
+    let bounding_boxes: Vec<_> = query
+        .iter()
+        .map(|(entity, shape, position, size, rotation, _)| {
+            let data = (*position, *size, *rotation).into();
+            (entity, shape.get_bounding_box(&data))
+        })
+        .collect();
+
+    for (entity1, entity2) in sweep_and_prune(&bounding_boxes) {
+        let Ok(
+            [
+                (_, shape1, position1, size1, rotation1, _),
+                (_, shape2, position2, size2, rotation2, _),
+            ],
+        ) = query.get_many([entity1, entity2])
+        else {
+            continue;
+        };
         let data1 = (*position1, *size1, *rotation1).into();
-        for (entity2, shape2, position2, size2, rotation2, _) in &query {
-            if entity1 == entity2 {
-                continue;
-            }
-            let data2 = (*position2, *size2, *rotation2).into();
+        let data2 = (*position2, *size2, *rotation2).into();
 
-            let Some(collision_data) = shape1.collides_with_shape(&data1, shape2, &data2) else {
-                continue;
-            };
+        let Some(collision_data) = shape1.collides_with_shape(&data1, shape2, &data2) else {
+            continue;
+        };
 
-            collisions.entry(entity1).or_default().push(collision_data);
-        }
+        // the collision seen from the other entity is the same, just in the opposite direction
+        let flipped_collision_data = CollisionData {
+            direction: -collision_data.direction,
+            ..collision_data
+        };
+        collisions.entry(entity1).or_default().push(collision_data);
+        collisions
+            .entry(entity2)
+            .or_default()
+            .push(flipped_collision_data);
     }
 
     for (_, _, _, _, _, mut color) in &mut query {
@@ -96,6 +118,10 @@ fn highlight_colliding(
         for collision_data in collision_data_list {
             let end = start + 10.0 * collision_data.depth * collision_data.direction * window.scale;
             gizmos.arrow_2d(start, end, Color::BLACK);
+
+            for contact_point in collision_data.contact_points.as_slice() {
+                gizmos.circle_2d(*contact_point * window.scale, 5.0, Color::BLACK);
+            }
         }
     }
 }
@@ -153,8 +179,12 @@ fn create_shape_mover(
 ) {
     let mouse_position = mouse_position_resource.0.as_dvec2();
 
-    let Some((clicked_entity, entity_position)) = get_clicked_entity(mouse_position, entity_query)
-    else {
+    let shapes = entity_query
+        .iter()
+        .map(|(entity, shape, position, size, rotation)| {
+            (entity, Some(shape), None, position, size, rotation)
+        });
+    let Some((clicked_entity, entity_position)) = get_clicked_entity(mouse_position, shapes) else {
         return;
     };
 
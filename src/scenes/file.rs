@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::{error, fmt, fs, io};
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{GameScene, despawn_scene, reset_environment};
+use crate::components::{
+    BodyKind, CollisionLayers, Connection, Density, IgnoreCollisions, Joint, JointKind,
+    PhysicsMaterial, PhysicsObject, Position, Rotation, Size, SpringForce, SpringLaw, Tangible,
+};
+use crate::physics::{AirResistance, Attractor, Drag, Gravity, GravityScale, MutualGravity};
+use crate::shapes::{Compound, Shape, ShapeImpl, SpringShape};
+use crate::spawners::{Spawner, spring::spring_bundle};
+
+/// The scene description loaded from the file given on the command line
+#[derive(Resource)]
+pub struct SceneFile(pub SceneDescription);
+
+#[derive(Component, Clone)]
+struct SceneFileEntity;
+
+pub struct SceneFilePlugin;
+
+impl Plugin for SceneFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameScene::File), scene_file_setup)
+            .add_systems(
+                OnExit(GameScene::File),
+                (despawn_scene::<SceneFileEntity>, reset_environment),
+            );
+    }
+}
+
+fn scene_file_setup(
+    scene_file: Res<SceneFile>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    debug!("Setting up scene from file");
+
+    scene_file
+        .0
+        .spawn(SceneFileEntity, &mut commands, &mut meshes, &mut materials);
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    Ron(ron::error::SpannedError),
+    Json(serde_json::Error),
+    UnknownFormat(PathBuf),
+    DuplicateName(String),
+    UnknownName(String),
+    CompoundDensity,
+    InvalidDensity(f64),
+    InvalidMass(f64),
+    InvalidSize([f64; 2]),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read scene file: {err}"),
+            Self::Ron(err) => write!(f, "invalid RON scene file: {err}"),
+            Self::Json(err) => write!(f, "invalid JSON scene file: {err}"),
+            Self::UnknownFormat(path) => write!(
+                f,
+                "scene file {} should end with .ron or .json",
+                path.display()
+            ),
+            Self::DuplicateName(name) => write!(f, "the name '{name}' is used more than once"),
+            Self::UnknownName(name) => {
+                write!(f, "a spring or joint is connected to unknown '{name}'")
+            }
+            Self::CompoundDensity => f.write_str(
+                "compound bodies get their mass from their shapes and can't have a density",
+            ),
+            Self::InvalidDensity(density) => {
+                write!(f, "density should be positive, but it is {density}")
+            }
+            Self::InvalidMass(mass) => write!(f, "mass should be positive, but it is {mass}"),
+            Self::InvalidSize([width, height]) => write!(
+                f,
+                "width and height should be positive, but they are {width} and {height}"
+            ),
+        }
+    }
+}
+
+impl error::Error for SceneFileError {}
+
+/// A scene written as data. Anchors and named bodies can be connected by
+/// springs and joints, which refer to them by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default = "default_gravity")]
+    pub gravity: [f64; 2],
+    /// Gravitation between every pair of bodies
+    #[serde(default)]
+    pub mutual_gravity: Option<MutualGravity>,
+    /// Drag felt by every body
+    #[serde(default)]
+    pub air_resistance: Drag,
+    #[serde(default)]
+    pub anchors: Vec<AnchorDescription>,
+    #[serde(default)]
+    pub attractors: Vec<AttractorDescription>,
+    #[serde(default)]
+    pub bodies: Vec<BodyDescription>,
+    #[serde(default)]
+    pub springs: Vec<SpringDescription>,
+    #[serde(default)]
+    pub joints: Vec<JointDescription>,
+}
+
+/// A fixed point that springs and joints can be attached to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnchorDescription {
+    pub name: String,
+    pub position: [f64; 2],
+}
+
+/// A fixed point mass that pulls every body towards it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttractorDescription {
+    pub position: [f64; 2],
+    pub strength: f64,
+    #[serde(default)]
+    pub softening: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyDescription {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Ignored by compound bodies
+    #[serde(default = "default_shape")]
+    pub shape: Shape,
+    /// The shapes a compound body is made of, each with its own mass. They
+    /// replace `shape` and `mass`, and are moved so the body's `position` is
+    /// their center of mass. Compound bodies can't have a `density`.
+    #[serde(default)]
+    pub compound: Option<Compound>,
+    pub position: [f64; 2],
+    #[serde(default = "default_size")]
+    pub size: [f64; 2],
+    #[serde(default)]
+    pub rotation: f64,
+    /// Ignored by compound bodies and bodies with a density
+    #[serde(default = "default_mass")]
+    pub mass: f64,
+    /// Mass per unit of area. The mass then comes from the area of the shape,
+    /// so it follows the size of the body.
+    #[serde(default)]
+    pub density: Option<f64>,
+    #[serde(default)]
+    pub velocity: [f64; 2],
+    #[serde(default)]
+    pub angular_velocity: f64,
+    /// Static bodies never move, and kinematic bodies keep their velocity.
+    /// Neither is moved by forces or pushed by other bodies.
+    #[serde(default)]
+    pub kind: BodyKind,
+    /// How strongly the body feels gravity. Zero turns gravity off for it
+    #[serde(default = "default_gravity_scale")]
+    pub gravity_scale: f64,
+    #[serde(default)]
+    pub drag: Option<Drag>,
+    /// sRGB color, from 0 to 255
+    #[serde(default = "default_body_color")]
+    pub color: [u8; 3],
+    /// Whether the body collides with other bodies
+    #[serde(default = "default_tangible")]
+    pub tangible: bool,
+    /// Which other tangible bodies it collides with
+    #[serde(default)]
+    pub collision_layers: Option<CollisionLayers>,
+    #[serde(default)]
+    pub material: Option<PhysicsMaterial>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpringDescription {
+    /// Name of an anchor or body
+    pub from: String,
+    /// Name of an anchor or body
+    pub to: String,
+    pub spring_constant: f64,
+    pub equilibrium_length: f64,
+    #[serde(default)]
+    pub damping: f64,
+    #[serde(default)]
+    pub law: SpringLaw,
+    /// Break the spring when it is stretched or compressed by more than this
+    /// fraction of its equilibrium length
+    #[serde(default)]
+    pub max_strain: Option<f64>,
+    /// Stop the connected bodies from colliding with each other
+    #[serde(default)]
+    pub ignore_collisions: bool,
+    #[serde(default = "default_spring_width")]
+    pub width: f64,
+    #[serde(default = "default_coil_count")]
+    pub coil_count: u32,
+    #[serde(default = "default_coil_diameter")]
+    pub coil_diameter: f32,
+    /// sRGB color, from 0 to 255
+    #[serde(default)]
+    pub color: [u8; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JointDescription {
+    /// Name of an anchor or body
+    pub from: String,
+    /// Name of an anchor or body
+    pub to: String,
+    pub kind: JointKind,
+    /// Where the joint is attached, relative to the first entity
+    #[serde(default)]
+    pub anchor1: [f64; 2],
+    /// Where the joint is attached, relative to the second entity
+    #[serde(default)]
+    pub anchor2: [f64; 2],
+    /// Rotation of the second entity relative to the first kept by prismatic
+    /// and weld joints
+    #[serde(default)]
+    pub reference_angle: f64,
+    /// Stop the connected bodies from colliding with each other, like the
+    /// limbs of a ragdoll
+    #[serde(default)]
+    pub ignore_collisions: bool,
+    #[serde(default = "default_joint_width")]
+    pub width: f64,
+    /// sRGB color, from 0 to 255
+    #[serde(default)]
+    pub color: [u8; 3],
+}
+
+fn default_gravity() -> [f64; 2] {
+    Gravity::default().0.to_array()
+}
+
+const fn default_shape() -> Shape {
+    Shape::Square
+}
+
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+const fn default_mass() -> f64 {
+    1.0
+}
+
+const fn default_size() -> [f64; 2] {
+    [1.0, 1.0]
+}
+
+const fn default_gravity_scale() -> f64 {
+    1.0
+}
+
+const fn default_body_color() -> [u8; 3] {
+    [10, 10, 200]
+}
+
+const fn default_tangible() -> bool {
+    true
+}
+
+const fn default_spring_width() -> f64 {
+    0.1
+}
+
+const fn default_joint_width() -> f64 {
+    0.05
+}
+
+const fn default_coil_count() -> u32 {
+    20
+}
+
+const fn default_coil_diameter() -> f32 {
+    0.01
+}
+
+impl SceneDescription {
+    /// Load a scene from a `.ron` or `.json` file
+    pub fn load(path: &Path) -> Result<Self, SceneFileError> {
+        let contents = fs::read_to_string(path).map_err(SceneFileError::Io)?;
+        match path.extension().and_then(OsStr::to_str) {
+            Some("ron") => Self::from_ron(&contents),
+            Some("json") => Self::from_json(&contents),
+            _ => Err(SceneFileError::UnknownFormat(path.to_owned())),
+        }
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, SceneFileError> {
+        let description: Self = ron::from_str(contents).map_err(SceneFileError::Ron)?;
+        description.validate()?;
+        Ok(description)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, SceneFileError> {
+        let description: Self = serde_json::from_str(contents).map_err(SceneFileError::Json)?;
+        description.validate()?;
+        Ok(description)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &String> {
+        self.anchors
+            .iter()
+            .map(|anchor| &anchor.name)
+            .chain(self.bodies.iter().filter_map(|body| body.name.as_ref()))
+    }
+
+    /// Names must be unique, and springs and joints must only refer to
+    /// existing names
+    fn validate(&self) -> Result<(), SceneFileError> {
+        let mut seen = Vec::new();
+        for name in self.names() {
+            if seen.contains(&name) {
+                return Err(SceneFileError::DuplicateName(name.clone()));
+            }
+            seen.push(name);
+        }
+
+        if self
+            .bodies
+            .iter()
+            .any(|body| body.compound.is_some() && body.density.is_some())
+        {
+            return Err(SceneFileError::CompoundDensity);
+        }
+        if let Some(density) = self
+            .bodies
+            .iter()
+            .filter_map(|body| body.density)
+            .find(|density| !is_positive(*density))
+        {
+            return Err(SceneFileError::InvalidDensity(density));
+        }
+        if let Some(body) = self.bodies.iter().find(|body| {
+            body.compound.is_none() && body.density.is_none() && !is_positive(body.mass)
+        }) {
+            return Err(SceneFileError::InvalidMass(body.mass));
+        }
+        if let Some(body) = self
+            .bodies
+            .iter()
+            .find(|body| !body.size.into_iter().all(is_positive))
+        {
+            return Err(SceneFileError::InvalidSize(body.size));
+        }
+
+        let connections = self
+            .springs
+            .iter()
+            .map(|spring| [&spring.from, &spring.to])
+            .chain(self.joints.iter().map(|joint| [&joint.from, &joint.to]));
+        for name in connections.flatten() {
+            if !seen.contains(&name) {
+                return Err(SceneFileError::UnknownName(name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawn the scene, with every entity tagged with `marker`. This also
+    /// sets the gravity of the world.
+    pub fn spawn<B: Bundle + Clone>(
+        &self,
+        marker: B,
+        commands: &mut Commands,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+    ) {
+        commands.insert_resource(Gravity(DVec2::from(self.gravity)));
+        if let Some(mutual_gravity) = self.mutual_gravity {
+            commands.insert_resource(mutual_gravity);
+        }
+        commands.insert_resource(AirResistance(self.air_resistance));
+
+        let mut entities = HashMap::new();
+
+        for attractor in &self.attractors {
+            commands.spawn((
+                marker.clone(),
+                Position(DVec2::from(attractor.position)),
+                Attractor {
+                    strength: attractor.strength,
+                    softening: attractor.softening,
+                },
+            ));
+        }
+
+        for anchor in &self.anchors {
+            let entity = commands
+                .spawn((marker.clone(), Position(DVec2::from(anchor.position))))
+                .id();
+            entities.insert(&anchor.name, entity);
+        }
+
+        for body in &self.bodies {
+            let [width, height] = body.size;
+            let [r, g, b] = body.color;
+            let mass = match (&body.compound, body.density) {
+                (Some(compound), _) => compound.mass(),
+                (None, Some(density)) => density * body.shape.get_area(DVec2::new(width, height)),
+                (None, None) => body.mass,
+            };
+
+            let mut spawner = Spawner::new(marker.clone(), commands).with_bundle((
+                Position(DVec2::from(body.position)),
+                Rotation(body.rotation),
+                Size { width, height },
+                PhysicsObject {
+                    velocity: DVec2::from(body.velocity),
+                    angular_velocity: body.angular_velocity,
+                    kind: body.kind,
+                    ..PhysicsObject::at_rest(mass)
+                },
+            ));
+            spawner = match &body.compound {
+                Some(compound) => spawner.with_compound(compound.clone(), meshes),
+                None => spawner.with_shape(body.shape, meshes),
+            }
+            .with_color(Color::srgb_u8(r, g, b), materials);
+            if body.tangible {
+                spawner = spawner.with_bundle(Tangible);
+            }
+            if let Some(layers) = body.collision_layers {
+                spawner = spawner.with_bundle(layers);
+            }
+            if let Some(material) = body.material {
+                spawner = spawner.with_bundle(material);
+            }
+            if let Some(drag) = body.drag {
+                spawner = spawner.with_bundle(drag);
+            }
+            if let Some(density) = body.density {
+                spawner = spawner.with_bundle(Density(density));
+            }
+            if body.gravity_scale != 1.0 {
+                spawner = spawner.with_bundle(GravityScale(body.gravity_scale));
+            }
+
+            let entity = spawner.id();
+            if let Some(name) = &body.name {
+                entities.insert(name, entity);
+            }
+        }
+
+        for spring in &self.springs {
+            let [r, g, b] = spring.color;
+            let (spring_marker, size, force, connection) = spring_bundle(
+                spring.width,
+                entities[&spring.from],
+                entities[&spring.to],
+                spring.damping,
+                spring.spring_constant,
+                spring.equilibrium_length,
+            );
+            let force = SpringForce {
+                law: spring.law,
+                max_strain: spring.max_strain,
+                ..force
+            };
+            let spawner = Spawner::new(marker.clone(), commands)
+                .with_bundle((spring_marker, size, force, connection))
+                .with_shape(
+                    Shape::Spring(SpringShape {
+                        coil_count: spring.coil_count,
+                        coil_diameter: spring.coil_diameter,
+                    }),
+                    meshes,
+                )
+                .with_color(Color::srgb_u8(r, g, b), materials)
+                .with_z_value(-1.0);
+            if spring.ignore_collisions {
+                spawner.with_bundle(IgnoreCollisions);
+            }
+        }
+
+        for joint in &self.joints {
+            let [r, g, b] = joint.color;
+            let spawner = Spawner::new(marker.clone(), commands)
+                .with_bundle((
+                    Joint {
+                        kind: joint.kind,
+                        anchor1: DVec2::from(joint.anchor1),
+                        anchor2: DVec2::from(joint.anchor2),
+                        reference_angle: joint.reference_angle,
+                    },
+                    Connection {
+                        entity1: entities[&joint.from],
+                        entity2: entities[&joint.to],
+                    },
+                    Size {
+                        width: 0.0,
+                        height: joint.width,
+                    },
+                ))
+                .with_shape(Shape::Square, meshes)
+                .with_color(Color::srgb_u8(r, g, b), materials)
+                .with_z_value(-1.0);
+            if joint.ignore_collisions {
+                spawner.with_bundle(IgnoreCollisions);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::bouncy_castle::BOUNCY_CASTLE;
+
+    const JSON_SCENE: &str = r#"{
+        "gravity": [0.0, -1.0],
+        "anchors": [{ "name": "ceiling", "position": [0.0, 2.0] }],
+        "bodies": [
+            { "name": "bob", "shape": "Circle", "position": [1.0, 2.0], "mass": 0.5 }
+        ],
+        "springs": [
+            { "from": "ceiling", "to": "bob", "spring_constant": 10.0, "equilibrium_length": 1.0 }
+        ]
+    }"#;
+
+    #[test]
+    fn test_json_and_ron_agree() {
+        let from_json = SceneDescription::from_json(JSON_SCENE).unwrap();
+        let from_ron = SceneDescription::from_ron(
+            r#"(
+                gravity: (0.0, -1.0),
+                anchors: [(name: "ceiling", position: (0.0, 2.0))],
+                bodies: [(name: Some("bob"), shape: Circle, position: (1.0, 2.0), mass: 0.5)],
+                springs: [(from: "ceiling", to: "bob", spring_constant: 10.0, equilibrium_length: 1.0)],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(from_json, from_ron);
+        assert_eq!(from_json.bodies[0].size, [1.0, 1.0]);
+        assert!(from_json.bodies[0].tangible);
+
+        let reloaded = SceneDescription::from_ron(&ron::to_string(&from_json).unwrap()).unwrap();
+        assert_eq!(from_json, reloaded);
+    }
+
+    #[test]
+    fn test_unknown_spring_connection() {
+        let scene = JSON_SCENE.replace(r#""to": "bob""#, r#""to": "bobby""#);
+        assert!(matches!(
+            SceneDescription::from_json(&scene),
+            Err(SceneFileError::UnknownName(name)) if name == "bobby"
+        ));
+    }
+
+    #[test]
+    fn test_unknown_joint_connection() {
+        let scene = SceneDescription::from_ron(
+            r#"(
+                anchors: [(name: "pivot", position: (0.0, 0.0))],
+                bodies: [(name: Some("rod"), shape: Square, position: (0.5, 0.0), mass: 1.0)],
+                joints: [(from: "pivot", to: "rod", kind: Revolute, anchor2: (-0.5, 0.0))],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(scene.joints[0].kind, JointKind::Revolute);
+
+        let mut scene = scene;
+        scene.joints[0].from = "hinge".to_owned();
+        let scene = ron::to_string(&scene).unwrap();
+        assert!(matches!(
+            SceneDescription::from_ron(&scene),
+            Err(SceneFileError::UnknownName(name)) if name == "hinge"
+        ));
+    }
+
+    #[test]
+    fn test_compound_with_density() {
+        let scene = r#"(
+            bodies: [(
+                compound: Some([(shape: Square, mass: 1.0), (shape: Circle, offset: (1.0, 0.0), mass: 1.0)]),
+                position: (0.0, 0.0),
+                density: Some(2.0),
+            )],
+        )"#;
+        assert!(matches!(
+            SceneDescription::from_ron(scene),
+            Err(SceneFileError::CompoundDensity)
+        ));
+        assert!(SceneDescription::from_ron(&scene.replace("density: Some(2.0),", "")).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_density() {
+        let scene = r#"{ "bodies": [{ "position": [0.0, 0.0], "density": 0.0 }] }"#;
+        assert!(matches!(
+            SceneDescription::from_json(scene),
+            Err(SceneFileError::InvalidDensity(density)) if density == 0.0
+        ));
+        let scene = scene.replace("0.0 }", "-1.5 }");
+        assert!(matches!(
+            SceneDescription::from_json(&scene),
+            Err(SceneFileError::InvalidDensity(density)) if density == -1.5
+        ));
+        let scene = scene.replace("-1.5 }", "2.5 }");
+        assert!(SceneDescription::from_json(&scene).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_mass() {
+        let scene = r#"{ "bodies": [{ "position": [0.0, 0.0], "mass": -1.0 }] }"#;
+        assert!(matches!(
+            SceneDescription::from_json(scene),
+            Err(SceneFileError::InvalidMass(mass)) if mass == -1.0
+        ));
+        // the mass is unused when it comes from the density
+        let scene = scene.replace("}]", r#", "density": 1.0 }]"#);
+        assert!(SceneDescription::from_json(&scene).is_ok());
+
+        let scene = "(bodies: [(position: (0.0, 0.0), mass: inf)])";
+        assert!(matches!(
+            SceneDescription::from_ron(scene),
+            Err(SceneFileError::InvalidMass(mass)) if mass.is_infinite()
+        ));
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let scene = r#"{ "bodies": [{ "position": [0.0, 0.0], "size": [1.0, 0.0] }] }"#;
+        assert!(matches!(
+            SceneDescription::from_json(scene),
+            Err(SceneFileError::InvalidSize([1.0, 0.0]))
+        ));
+        let scene = scene.replace("0.0] }", "2.0] }");
+        assert!(SceneDescription::from_json(&scene).is_ok());
+    }
+
+    #[test]
+    fn test_bouncy_castle_file() {
+        let scene = SceneDescription::from_ron(BOUNCY_CASTLE).unwrap();
+        assert_eq!(scene.bodies.len(), 5);
+        assert_eq!(scene.springs.len(), 9);
+    }
+}
@@ -1,21 +1,45 @@
 mod bouncy_castle;
 mod collision_test;
+mod file;
+mod n_body;
 mod select;
 mod shapes;
 mod spring_pendulum;
 
 use bouncy_castle::BouncyCastlePlugin;
 use collision_test::CollisionTestPlugin;
+use file::SceneFilePlugin;
+use n_body::NBodyPlugin;
 use select::SelectPlugin;
 use shapes::ShapesPlugin;
 use spring_pendulum::SpringPendulumPlugin;
 
+pub use file::{SceneDescription, SceneFile};
+
 use std::fmt;
 
 use bevy::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, States, EnumIter)]
+use crate::physics::{AirResistance, Gravity, MutualGravity};
+use crate::replay::{InputQueue, SimulationInput};
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    States,
+    EnumIter,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
 pub enum GameScene {
     #[default]
     Select,
@@ -23,6 +47,15 @@ pub enum GameScene {
     BouncyCastle,
     Shapes,
     CollisionTest,
+    NBody,
+    /// A scene loaded from the file given on the command line
+    #[strum(disabled)]
+    #[value(skip)]
+    File,
+    /// A scene restored from a snapshot
+    #[strum(disabled)]
+    #[value(skip)]
+    Snapshot,
 }
 
 impl fmt::Display for GameScene {
@@ -33,10 +66,24 @@ impl fmt::Display for GameScene {
             Self::BouncyCastle => f.write_str("Bouncy Castle"),
             Self::Shapes => f.write_str("Shapes"),
             Self::CollisionTest => f.write_str("Collision Test"),
+            Self::NBody => f.write_str("N-Body"),
+            Self::File => f.write_str("Scene File"),
+            Self::Snapshot => f.write_str("Snapshot"),
         }
     }
 }
 
+impl GameScene {
+    /// Whether the scene only needs the physics plugins, and can therefore
+    /// run without a window
+    pub const fn is_simulation(self) -> bool {
+        matches!(
+            self,
+            Self::SpringPendulum | Self::BouncyCastle | Self::NBody | Self::File | Self::Snapshot
+        )
+    }
+}
+
 #[derive(Component)]
 struct SceneButton(GameScene);
 
@@ -47,14 +94,35 @@ impl Plugin for ScenePlugin {
         app.add_plugins((
             back_plugin,
             SelectPlugin,
-            SpringPendulumPlugin,
-            BouncyCastlePlugin,
+            SimulationScenePlugin,
             ShapesPlugin,
             CollisionTestPlugin,
         ));
     }
 }
 
+/// The scenes where `GameScene::is_simulation` is true, without any UI
+pub struct SimulationScenePlugin;
+
+impl Plugin for SimulationScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            SpringPendulumPlugin,
+            BouncyCastlePlugin,
+            NBodyPlugin,
+            SceneFilePlugin,
+        ));
+    }
+}
+
+/// Scenes that change the gravity or air resistance should reset them when
+/// exiting, as other scenes expect the defaults
+pub fn reset_environment(mut commands: Commands) {
+    commands.insert_resource(Gravity::default());
+    commands.remove_resource::<MutualGravity>();
+    commands.insert_resource(AirResistance::default());
+}
+
 pub fn despawn_scene<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
         commands.entity(entity).despawn();
@@ -106,13 +174,13 @@ fn add_back_button(mut commands: Commands) {
 
 fn scene_button_system(
     mut query: Query<(&Interaction, &SceneButton, &mut BorderColor), Changed<Interaction>>,
-    mut game_scene: ResMut<NextState<GameScene>>,
+    mut queue: ResMut<InputQueue>,
 ) {
     for (interaction, scene, mut color) in &mut query {
         match interaction {
             Interaction::Pressed => {
                 *color = Color::srgb_u8(100, 100, 200).into();
-                game_scene.set(scene.0);
+                queue.push(SimulationInput::SwitchScene(scene.0));
             }
             Interaction::Hovered => {
                 *color = Color::srgb_u8(150, 150, 150).into();
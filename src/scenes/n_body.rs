@@ -0,0 +1,89 @@
+use std::f64::consts::PI;
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use super::{GameScene, despawn_scene, reset_environment};
+use crate::components::{PhysicsObject, Position, Size};
+use crate::physics::{Gravity, MutualGravity};
+use crate::shapes::Shape;
+use crate::spawners::Spawner;
+
+const STAR_MASS: f64 = 20.0;
+const PLANET_COUNT: u32 = 150;
+const PLANET_MASS: f64 = 0.01;
+const INNER_RADIUS: f64 = 1.0;
+const OUTER_RADIUS: f64 = 3.5;
+
+#[derive(Component, Clone)]
+struct NBodyEntity;
+
+pub struct NBodyPlugin;
+
+impl Plugin for NBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameScene::NBody), n_body_setup)
+            .add_systems(
+                OnExit(GameScene::NBody),
+                (despawn_scene::<NBodyEntity>, reset_environment),
+            );
+    }
+}
+
+/// A heavy star orbited by a disk of light planets, which all pull on each
+/// other. The planets start on circular orbits around the mass inside them.
+pub fn n_body_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    debug!("Setting up n-body");
+
+    let mutual_gravity = MutualGravity {
+        constant: 1.0,
+        softening: 0.05,
+        theta: 0.5,
+    };
+    commands.insert_resource(Gravity(DVec2::ZERO));
+    commands.insert_resource(mutual_gravity);
+
+    Spawner::new(NBodyEntity, &mut commands)
+        .with_bundle((
+            Position(DVec2::ZERO),
+            Size {
+                width: 0.3,
+                height: 0.3,
+            },
+            PhysicsObject::at_rest(STAR_MASS),
+        ))
+        .with_shape(Shape::Circle, &mut meshes)
+        .with_color(Color::srgb_u8(230, 160, 20), &mut materials);
+
+    // golden angle steps spread the planets evenly without any randomness
+    let golden_angle = PI * (3.0 - 5f64.sqrt());
+    for i in 0..PLANET_COUNT {
+        let t = (f64::from(i) + 0.5) / f64::from(PLANET_COUNT);
+        let radius = INNER_RADIUS + (OUTER_RADIUS - INNER_RADIUS) * t.sqrt();
+        let direction = DVec2::from_angle(f64::from(i) * golden_angle);
+        let enclosed_mass = STAR_MASS + f64::from(i) * PLANET_MASS;
+        let speed = (mutual_gravity.constant * enclosed_mass / radius).sqrt();
+
+        Spawner::new(NBodyEntity, &mut commands)
+            .with_bundle((
+                Position(radius * direction),
+                Size {
+                    width: 0.06,
+                    height: 0.06,
+                },
+                PhysicsObject {
+                    velocity: speed * direction.perp(),
+                    ..PhysicsObject::at_rest(PLANET_MASS)
+                },
+            ))
+            .with_shape(Shape::Circle, &mut meshes)
+            .with_color(
+                Color::srgb(0.1, 0.2 + 0.6 * t as f32, 0.8 - 0.4 * t as f32),
+                &mut materials,
+            );
+    }
+}
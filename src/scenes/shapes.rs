@@ -3,7 +3,7 @@ use std::iter::zip;
 use crate::MousePosition;
 use crate::components::{Position, Rotation, Size, Tangible};
 use crate::debug::bounding_box::BoundingBoxColor;
-use crate::shapes::{Shape, ShapeImpl};
+use crate::shapes::{Polygon, RoundedBox, Shape, ShapeImpl};
 
 use bevy::math::DVec2;
 use bevy::prelude::*;
@@ -58,6 +58,12 @@ pub fn shapes_setup(
         Shape::Heptagon,
         Shape::Octagon,
         Shape::Circle,
+        Shape::Capsule,
+        Shape::RoundedBox(RoundedBox { corner_radius: 0.4 }),
+        Shape::Polygon(
+            Polygon::new(&[[-0.5, -0.5], [0.5, -0.5], [-0.5, 0.5]])
+                .expect("a wedge is a valid polygon"),
+        ),
     ];
 
     let start_color = LinearRgba::from(Srgba::rgb_u8(91, 206, 250));
@@ -67,8 +73,9 @@ pub fn shapes_setup(
         .map(|i| start_color.mix(&end_color, i as f32 / (shapes.len() - 1) as f32))
         .collect();
 
-    let size = 0.75;
-    let step_size = size * 1.25;
+    // the screen is a bit over 7 units wide, so the row is kept 6 units long
+    let step_size = (6.0 / shapes.len() as f64).min(0.9375);
+    let size = step_size / 1.25;
     let start = -((shapes.len() - 1) as f64 * 0.5 * step_size);
 
     for (i, (color, shape)) in zip(colors, shapes).enumerate() {
@@ -5,7 +5,7 @@ use bevy::math::DVec2;
 use bevy::prelude::*;
 
 use super::{GameScene, despawn_scene};
-use crate::spawners::{Spawner, spring::spring_bundle, square::physics_square_bundle};
+use crate::spawners::{Spawner, spring::spring_bundle, square::dense_square_bundle};
 
 #[derive(Component)]
 struct SpringPendulumEntity;
@@ -36,8 +36,8 @@ pub fn spring_pendulum_setup(
 
     for i in 0..3 {
         let entity2 = Spawner::new(SpringPendulumEntity, &mut commands)
-            .with_bundle(physics_square_bundle(
-                0.1,
+            .with_bundle(dense_square_bundle(
+                0.4,
                 0.5,
                 0.5,
                 DVec2::new(i as f64 + 1.0, 2.0),
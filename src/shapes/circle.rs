@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use std::f64::consts::PI;
 
-use crate::shapes::{CollisionData, Shape, ShapeData, ShapeImpl, ngon::NGon, transform_point};
+use crate::shapes::{
+    CollisionData, ContactPoints, Shape, ShapeData, ShapeImpl, ngon::NGon, transform_point,
+};
 use crate::utils::{
     BoundingBox, DEdge, Edge, ShapeProjection, ToVec, ToVector, WrappingWindows,
     global_newton_solver, solve_quadratic,
@@ -18,25 +20,36 @@ impl Circle {
     // TODO: Set this to ∞
     const VERTICES: u8 = 30;
 
-    fn is_circular(data: &ShapeData) -> bool {
+    pub(super) fn is_circular(data: &ShapeData) -> bool {
         // is width ≈ height?
         (data.size.x - data.size.y).abs() < 1e-6
     }
 
+    /// Get the point on the edge of the ellipse that is furthest along `direction`.
+    fn support_point(data: &ShapeData, direction: DVec2) -> DVec2 {
+        let semi_axes = 0.5 * data.size;
+        let local_direction = DVec2::from_angle(-data.rotation).rotate(direction);
+        let local_point =
+            semi_axes * semi_axes * local_direction / (semi_axes * local_direction).length();
+        data.position + DVec2::from_angle(data.rotation).rotate(local_point)
+    }
+
     fn circles_collide(self_data: &ShapeData, other_data: &ShapeData) -> Option<CollisionData> {
         #![allow(non_snake_case)]
 
         if Self::is_circular(self_data) && Self::is_circular(other_data) {
             // circles are easy as they have a constant radius
-            let self_to_other = other_data.position - self_data.position;
+            let other_to_self = self_data.position - other_data.position;
             let self_r = 0.5 * self_data.size.x;
-            let other_r = 0.5 * self_data.size.x;
-            let overlap = (self_r + other_r) - self_to_other.length();
+            let other_r = 0.5 * other_data.size.x;
+            let overlap = (self_r + other_r) - other_to_self.length();
             if overlap > 0.0 {
-                return Some(CollisionData {
-                    depth: overlap as f32,
-                    direction: self_to_other.normalize().as_vec2(),
-                });
+                let direction = other_to_self.normalize_or(DVec2::Y);
+                let contact_point = self_data.position - direction * (self_r - 0.5 * overlap);
+                return Some(
+                    CollisionData::new(overlap as f32, direction.as_vec2())
+                        .with_contact_points(ContactPoints::one(contact_point.as_vec2())),
+                );
             } else {
                 return None;
             };
@@ -174,10 +187,10 @@ impl Circle {
 
         let depth = (point1 - point2).length();
 
-        Some(CollisionData::new(
-            depth as f32,
-            collision_direction.as_vec2(),
-        ))
+        Some(
+            CollisionData::new(depth as f32, collision_direction.as_vec2())
+                .with_contact_points(ContactPoints::one(collision_point.as_vec2())),
+        )
     }
 }
 
@@ -203,6 +216,20 @@ impl ShapeImpl for Circle {
         BoundingBox::from_center_size(data.position, DVec2::new(bb_width, bb_height))
     }
 
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // I = m(a² + b²)/4, where a and b are the semi-axes
+        mass * (0.5 * size).length_squared() / 4.0
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        // A = πab, where a and b are the semi-axes
+        PI * size.x * size.y / 4.0
+    }
+
+    fn get_centroid(&self, _size: DVec2) -> DVec2 {
+        DVec2::ZERO
+    }
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
         if self.point_definitely_outside(data, point) {
             return false;
@@ -292,10 +319,13 @@ impl ShapeImpl for Circle {
                 collision_direction = -collision_direction;
             }
 
-            Some(CollisionData {
-                depth: min_depth,
-                direction: collision_direction,
-            })
+            // the deepest point of the ellipse is the one furthest towards the other shape
+            let contact_point = Self::support_point(data, -collision_direction.as_dvec2());
+
+            Some(
+                CollisionData::new(min_depth, collision_direction)
+                    .with_contact_points(ContactPoints::one(contact_point.as_vec2())),
+            )
         }
     }
 }
@@ -0,0 +1,319 @@
+use std::{error, fmt};
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, Mesh, VertexAttributeValues};
+use serde::{Deserialize, Serialize};
+
+use super::{Shape, ShapeData, ShapeImpl, incomplete_mesh};
+use crate::components::{Position, Rotation, Size};
+use crate::utils::BoundingBox;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompoundError {
+    NoChildren,
+    NotPositiveMass,
+    Spring,
+}
+
+impl fmt::Display for CompoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoChildren => f.write_str("a compound needs at least one shape"),
+            Self::NotPositiveMass => f.write_str("every shape in a compound needs a positive mass"),
+            Self::Spring => f.write_str("springs can't be part of a compound"),
+        }
+    }
+}
+
+impl error::Error for CompoundError {}
+
+/// One of the shapes a compound is made of
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompoundChild {
+    pub shape: Shape,
+    /// Position relative to the center of mass of the compound
+    #[serde(default)]
+    pub offset: [f64; 2],
+    /// Rotation relative to the compound
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "default_size")]
+    pub size: [f64; 2],
+    pub mass: f64,
+}
+
+impl CompoundChild {
+    /// Center of mass of the child, for a compound with the given size
+    fn center_of_mass(&self, size: DVec2) -> DVec2 {
+        let centroid = self.shape.get_centroid(DVec2::from(self.size) * size);
+        DVec2::from(self.offset) * size + DVec2::from_angle(self.rotation).rotate(centroid)
+    }
+}
+
+const fn default_size() -> [f64; 2] {
+    [1.0, 1.0]
+}
+
+/// A body made of several shapes that move together as one `PhysicsObject`,
+/// like a dumbbell or a table. The offsets and sizes of the children are
+/// scaled by the `Size` of the entity, like the vertices of a shape.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<CompoundChild>", into = "Vec<CompoundChild>")]
+#[require(Position, Rotation, Size)]
+pub struct Compound {
+    children: Vec<CompoundChild>,
+}
+
+impl Compound {
+    /// Create a compound, moving the children so their center of mass is at
+    /// the origin. Compounds that are already centered are left alone, so
+    /// saving and loading one gives back the same offsets.
+    pub fn new(mut children: Vec<CompoundChild>) -> Result<Self, CompoundError> {
+        if children.is_empty() {
+            return Err(CompoundError::NoChildren);
+        }
+        for child in &children {
+            if !(child.mass.is_finite() && child.mass > 0.0) {
+                return Err(CompoundError::NotPositiveMass);
+            }
+            if matches!(child.shape, Shape::Spring(_)) {
+                return Err(CompoundError::Spring);
+            }
+        }
+
+        let mass: f64 = children.iter().map(|child| child.mass).sum();
+        let center = children
+            .iter()
+            .map(|child| child.mass * child.center_of_mass(DVec2::ONE))
+            .sum::<DVec2>()
+            / mass;
+        let extent = children
+            .iter()
+            .map(|child| DVec2::from(child.offset).abs().max_element())
+            .fold(0.0, f64::max);
+        if center.length() > f64::EPSILON * extent {
+            for child in &mut children {
+                child.offset = (DVec2::from(child.offset) - center).to_array();
+            }
+        }
+
+        Ok(Self { children })
+    }
+
+    pub fn children(&self) -> &[CompoundChild] {
+        &self.children
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.children.iter().map(|child| child.mass).sum()
+    }
+
+    /// Moment of inertia around the center of mass, from the moments of the
+    /// children with the parallel axis theorem
+    pub fn get_moment_of_inertia(&self, size: DVec2) -> f64 {
+        self.children
+            .iter()
+            .map(|child| {
+                child
+                    .shape
+                    .get_moment_of_inertia(child.mass, DVec2::from(child.size) * size)
+                    + child.mass * child.center_of_mass(size).length_squared()
+            })
+            .sum()
+    }
+
+    /// The children, placed in the world
+    pub fn placed_children(&self, data: &ShapeData) -> impl Iterator<Item = (Shape, ShapeData)> {
+        let rotation = DVec2::from_angle(data.rotation);
+        self.children.iter().map(move |child| {
+            (
+                child.shape,
+                ShapeData {
+                    position: data.position
+                        + rotation.rotate(DVec2::from(child.offset) * data.size),
+                    rotation: data.rotation + child.rotation,
+                    size: DVec2::from(child.size) * data.size,
+                },
+            )
+        })
+    }
+
+    /// A mesh of every child, for a compound with the given size. Like the
+    /// meshes of rounded shapes, it is only right for that size.
+    pub fn get_sized_mesh(&self, size: DVec2) -> Mesh {
+        let data = ShapeData {
+            position: DVec2::ZERO,
+            rotation: 0.0,
+            size,
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (shape, child_data) in self.placed_children(&data) {
+            let mesh = shape.get_sized_mesh(child_data.size);
+            let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+            else {
+                continue;
+            };
+
+            let first = vertices.len() as u32;
+            if let Some(mesh_indices) = mesh.indices() {
+                indices.extend(mesh_indices.iter().map(|index| first + index as u32));
+            }
+            let rotation = DVec2::from_angle(child_data.rotation);
+            vertices.extend(positions.iter().map(|[x, y, _]| {
+                let vertex = DVec2::new(f64::from(*x), f64::from(*y)) * child_data.size;
+                // the mesh is stretched to the size of the entity
+                ((child_data.position + rotation.rotate(vertex)) / size)
+                    .as_vec2()
+                    .to_array()
+            }));
+        }
+
+        incomplete_mesh(&vertices).with_inserted_indices(Indices::U32(indices))
+    }
+}
+
+impl TryFrom<Vec<CompoundChild>> for Compound {
+    type Error = CompoundError;
+
+    fn try_from(value: Vec<CompoundChild>) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<Compound> for Vec<CompoundChild> {
+    fn from(value: Compound) -> Self {
+        value.children
+    }
+}
+
+/// Every shape an entity is made of, placed in the world. Entities have
+/// either a single `Shape` or a `Compound`.
+pub fn placed_shapes(
+    shape: Option<&Shape>,
+    compound: Option<&Compound>,
+    data: ShapeData,
+) -> Vec<(Shape, ShapeData)> {
+    match (shape, compound) {
+        (_, Some(compound)) => compound.placed_children(&data).collect(),
+        (Some(shape), None) => vec![(*shape, data)],
+        (None, None) => Vec::new(),
+    }
+}
+
+/// The bounding box around every shape, or `None` if there are no shapes
+pub fn combined_bounding_box(shapes: &[(Shape, ShapeData)]) -> Option<BoundingBox> {
+    shapes
+        .iter()
+        .map(|(shape, data)| shape.get_bounding_box(data))
+        .reduce(|combined, bounding_box| BoundingBox {
+            min: combined.min.min(bounding_box.min),
+            max: combined.max.max(bounding_box.max),
+        })
+}
+
+/// Remake the meshes of compounds when their size changes, so that rounded
+/// children stay round and rotated children aren't sheared
+#[allow(clippy::type_complexity)]
+pub fn update_compound_meshes(
+    query: Query<(&Compound, &Size, &Mesh2d), Or<(Changed<Compound>, Changed<Size>)>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (compound, size, mesh) in &query {
+        if let Some(mesh) = meshes.get_mut(&mesh.0) {
+            *mesh = compound.get_sized_mesh(DVec2::from(*size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::assert_close;
+
+    use super::*;
+
+    fn dumbbell() -> Compound {
+        let bell = |x, mass| CompoundChild {
+            shape: Shape::Circle,
+            offset: [x, 0.0],
+            rotation: 0.0,
+            size: [0.5, 0.5],
+            mass,
+        };
+        Compound::new(vec![
+            bell(0.0, 1.0),
+            CompoundChild {
+                shape: Shape::Square,
+                offset: [1.0, 0.0],
+                rotation: 0.0,
+                size: [2.0, 0.1],
+                mass: 0.5,
+            },
+            bell(2.0, 1.0),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_centered_on_center_of_mass() {
+        let compound = dumbbell();
+        let offsets: Vec<_> = compound
+            .children()
+            .iter()
+            .map(|child| child.offset)
+            .collect();
+        assert_eq!(offsets, [[-1.0, 0.0], [0.0, 0.0], [1.0, 0.0]]);
+        assert_close!(compound.mass(), 2.5, 1e-12);
+
+        let reloaded = Compound::new(compound.children().to_vec()).unwrap();
+        assert_eq!(reloaded, compound);
+
+        assert_eq!(Compound::new(Vec::new()), Err(CompoundError::NoChildren));
+        let mut children = compound.children().to_vec();
+        children[1].mass = 0.0;
+        assert_eq!(Compound::new(children), Err(CompoundError::NotPositiveMass));
+    }
+
+    #[test]
+    fn test_moment_of_inertia() {
+        let compound = dumbbell();
+        let size = DVec2::new(2.0, 1.0);
+
+        // each bell is a circle 2 units from the center, and the bar is a
+        // rectangle 4 units long
+        let bell = 1.0 * (0.5f64 * 0.5 + 1.0 * 1.0) / 16.0 + 1.0 * 2.0 * 2.0;
+        let bar = 0.5 * (4.0 * 4.0 + 0.1 * 0.1) / 12.0;
+        assert_close!(
+            compound.get_moment_of_inertia(size),
+            2.0 * bell + bar,
+            1e-12
+        );
+    }
+
+    #[test]
+    fn test_placed_children() {
+        let compound = dumbbell();
+        let data = ShapeData {
+            position: DVec2::new(1.0, 1.0),
+            rotation: PI / 2.0,
+            size: DVec2::ONE,
+        };
+
+        let shapes = placed_shapes(None, Some(&compound), data);
+        assert_eq!(shapes.len(), 3);
+        let (shape, right) = &shapes[2];
+        assert_eq!(*shape, Shape::Circle);
+        assert_close!(right.position.x, 1.0, 1e-12);
+        assert_close!(right.position.y, 2.0, 1e-12);
+
+        let bounding_box = combined_bounding_box(&shapes).unwrap();
+        assert_close!(bounding_box.min.y, -0.25, 1e-12);
+        assert_close!(bounding_box.max.y, 2.25, 1e-12);
+    }
+}
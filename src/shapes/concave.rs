@@ -0,0 +1,489 @@
+use bevy::math::{DVec2, Vec2};
+use bevy::render::mesh::{Indices, Mesh};
+use serde::{Deserialize, Serialize};
+
+use super::polygon::{MAX_VERTICES, center_on_centroid};
+use super::{CollisionData, ContactPoints, Polygon, PolygonError, Shape, ShapeData, ShapeImpl};
+use crate::utils::{BoundingBox, WrappingWindows};
+
+/// Collisions with different pieces that push within this cosine of the same
+/// direction share their contact points
+const SAME_DIRECTION: f32 = 0.99;
+
+/// A polygon that doesn't need to be convex, with vertices ordered
+/// counter-clockwise. It is split into convex pieces when it is created, by
+/// clipping ears off the outline and then merging the triangles back together
+/// for as long as they stay convex.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<[f32; 2]>", into = "Vec<[f32; 2]>")]
+pub struct ConcavePolygon {
+    vertices: [[f32; 2]; MAX_VERTICES],
+    len: u8,
+    /// Bit masks of the vertices that make up each convex piece. The vertices
+    /// of a piece are in the same order as in the outline.
+    pieces: [u16; MAX_VERTICES - 2],
+    piece_count: u8,
+}
+
+impl ConcavePolygon {
+    /// Create a polygon, moving the vertices so their centroid is at the
+    /// origin, and split it into convex pieces
+    pub fn new(vertices: &[[f32; 2]]) -> Result<Self, PolygonError> {
+        if vertices.len() < 3 {
+            return Err(PolygonError::TooFewVertices);
+        }
+        if vertices.len() > MAX_VERTICES {
+            return Err(PolygonError::TooManyVertices);
+        }
+
+        let mut points: Vec<_> = vertices.iter().map(|v| Vec2::from_array(*v)).collect();
+        if !is_simple(&points) {
+            return Err(PolygonError::SelfIntersecting);
+        }
+        let area: f32 = points
+            .wrapping_windows::<2>()
+            .map(|[v1, v2]| v1.perp_dot(*v2))
+            .sum();
+        if area < 0.0 {
+            return Err(PolygonError::Clockwise);
+        }
+        center_on_centroid(&mut points);
+
+        let triangles = triangulate(&points).ok_or(PolygonError::SelfIntersecting)?;
+        let pieces = merge_pieces(&points, triangles.into_iter().map(Vec::from).collect());
+
+        let mut polygon = Self {
+            vertices: [[0.0; 2]; MAX_VERTICES],
+            len: points.len() as u8,
+            pieces: [0; MAX_VERTICES - 2],
+            piece_count: pieces.len() as u8,
+        };
+        for (vertex, point) in polygon.vertices.iter_mut().zip(points) {
+            *vertex = point.to_array();
+        }
+        for (mask, piece) in polygon.pieces.iter_mut().zip(pieces) {
+            *mask = piece.iter().fold(0, |mask, i| mask | 1 << i);
+        }
+        Ok(polygon)
+    }
+
+    pub fn vertices(&self) -> &[[f32; 2]] {
+        &self.vertices[..self.len as usize]
+    }
+
+    /// The convex pieces, each centered on its own centroid, along with where
+    /// that centroid is
+    fn pieces(&self) -> impl Iterator<Item = (Polygon, Vec2)> + '_ {
+        self.pieces[..self.piece_count as usize].iter().map(|mask| {
+            let points: Vec<_> = self
+                .vertices()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & 1 << i != 0)
+                .map(|(_, vertex)| Vec2::from_array(*vertex))
+                .collect();
+            Polygon::from_convex(&points)
+        })
+    }
+}
+
+/// Where a piece centered at `offset` is, given where the whole shape is
+fn piece_data(data: &ShapeData, offset: Vec2) -> ShapeData {
+    let offset = DVec2::from_angle(data.rotation).rotate(data.size * offset.as_dvec2());
+    ShapeData {
+        position: data.position + offset,
+        ..*data
+    }
+}
+
+fn turn(v1: Vec2, v2: Vec2, v3: Vec2) -> f32 {
+    (v2 - v1).perp_dot(v3 - v2)
+}
+
+fn segments_intersect(p1: Vec2, p2: Vec2, q1: Vec2, q2: Vec2) -> bool {
+    let side = |a: Vec2, b: Vec2, c: Vec2| (b - a).perp_dot(c - a);
+    let (d1, d2) = (side(q1, q2, p1), side(q1, q2, p2));
+    let (d3, d4) = (side(p1, p2, q1), side(p1, p2, q2));
+    if d1 == 0.0 && d2 == 0.0 {
+        // the segments are on the same line, so they intersect if their
+        // projections onto it overlap
+        let direction = p2 - p1;
+        let (t1, t2) = ((q1 - p1).dot(direction), (q2 - p1).dot(direction));
+        return t1.min(t2) <= direction.length_squared() && t1.max(t2) >= 0.0;
+    }
+    d1 * d2 <= 0.0 && d3 * d4 <= 0.0
+}
+
+/// Whether the outline never crosses or touches itself
+fn is_simple(points: &[Vec2]) -> bool {
+    let n = points.len();
+    let edge = |i: usize| (points[i], points[(i + 1) % n]);
+
+    // neighbouring edges only meet at their shared vertex, unless the outline
+    // turns straight back on itself
+    let folds = points
+        .wrapping_windows::<3>()
+        .any(|[v1, v2, v3]| turn(*v1, *v2, *v3) == 0.0 && (v2 - v1).dot(v3 - v2) <= 0.0);
+
+    let crossings = (0..n).any(|i| {
+        ((i + 2)..n).filter(|j| (j + 1) % n != i).any(|j| {
+            let ((p1, p2), (q1, q2)) = (edge(i), edge(j));
+            segments_intersect(p1, p2, q1, q2)
+        })
+    });
+
+    !folds && !crossings
+}
+
+/// Split the outline into triangles by repeatedly clipping off a corner that
+/// has no other vertex inside it. The triangles are lists of vertex indices in
+/// increasing order, which is counter-clockwise.
+fn triangulate(points: &[Vec2]) -> Option<Vec<[usize; 3]>> {
+    let in_triangle = |p: Vec2, [a, b, c]: [Vec2; 3]| {
+        turn(a, b, p) >= 0.0 && turn(b, c, p) >= 0.0 && turn(c, a, p) >= 0.0
+    };
+
+    let mut remaining: Vec<_> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() >= 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        let ear = (0..n).find(|&i| {
+            let indices = corner(i);
+            let triangle = indices.map(|index| points[index]);
+            turn(triangle[0], triangle[1], triangle[2]) > 0.0
+                && remaining
+                    .iter()
+                    .filter(|index| !indices.contains(index))
+                    .all(|index| !in_triangle(points[*index], triangle))
+        })?;
+
+        let mut triangle = corner(ear);
+        triangle.sort_unstable();
+        triangles.push(triangle);
+        remaining.remove(ear);
+    }
+    Some(triangles)
+}
+
+fn is_convex(points: &[Vec2], piece: &[usize]) -> bool {
+    piece
+        .wrapping_windows::<3>()
+        .all(|[i1, i2, i3]| turn(points[*i1], points[*i2], points[*i3]) > 0.0)
+}
+
+/// Whether two vertices are next to each other in a piece
+fn has_edge(piece: &[usize], a: usize, b: usize) -> bool {
+    piece
+        .wrapping_windows::<2>()
+        .any(|[i1, i2]| (*i1, *i2) == (a, b) || (*i1, *i2) == (b, a))
+}
+
+/// Remove the edges between pieces that aren't needed to keep every piece
+/// convex
+fn merge_pieces(points: &[Vec2], mut pieces: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+    'merging: loop {
+        for i in 0..pieces.len() {
+            for j in (i + 1)..pieces.len() {
+                let shared: Vec<_> = pieces[i]
+                    .iter()
+                    .filter(|index| pieces[j].contains(index))
+                    .copied()
+                    .collect();
+                let [a, b] = shared[..] else {
+                    continue;
+                };
+                if !has_edge(&pieces[i], a, b) || !has_edge(&pieces[j], a, b) {
+                    continue;
+                }
+
+                let mut merged: Vec<_> = pieces[i].iter().chain(&pieces[j]).copied().collect();
+                merged.sort_unstable();
+                merged.dedup();
+                if is_convex(points, &merged) {
+                    pieces[i] = merged;
+                    pieces.swap_remove(j);
+                    continue 'merging;
+                }
+            }
+        }
+        return pieces;
+    }
+}
+
+/// The collision with the deepest piece. Contact points of other pieces that
+/// are pushed the same way are kept too, so that a flat side spanning several
+/// pieces gets a contact point at both ends.
+fn merge_collisions(collisions: &[CollisionData]) -> Option<CollisionData> {
+    let deepest = collisions
+        .iter()
+        .max_by(|a, b| a.depth.total_cmp(&b.depth))?;
+    let tangent = deepest.direction.perp();
+
+    let points: Vec<_> = collisions
+        .iter()
+        .filter(|collision| collision.direction.dot(deepest.direction) > SAME_DIRECTION)
+        .flat_map(|collision| collision.contact_points.as_slice().to_vec())
+        .collect();
+    let along = |a: &&Vec2, b: &&Vec2| a.dot(tangent).total_cmp(&b.dot(tangent));
+    let (Some(first), Some(last)) = (points.iter().min_by(along), points.iter().max_by(along))
+    else {
+        return Some(*deepest);
+    };
+
+    let contact_points = if first.distance(*last) < 1e-6 {
+        ContactPoints::one(*first)
+    } else {
+        ContactPoints::two(*first, *last)
+    };
+    Some(deepest.with_contact_points(contact_points))
+}
+
+impl TryFrom<Vec<[f32; 2]>> for ConcavePolygon {
+    type Error = PolygonError;
+
+    fn try_from(value: Vec<[f32; 2]>) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<ConcavePolygon> for Vec<[f32; 2]> {
+    fn from(value: ConcavePolygon) -> Self {
+        value.vertices().to_vec()
+    }
+}
+
+impl ShapeImpl for ConcavePolygon {
+    fn get_vertices(&self) -> Vec<[f32; 2]> {
+        self.vertices().to_vec()
+    }
+
+    fn get_mesh(&self) -> Mesh {
+        let points: Vec<_> = self
+            .vertices()
+            .iter()
+            .map(|vertex| Vec2::from_array(*vertex))
+            .collect();
+        let indices = triangulate(&points)
+            .expect("the polygon was triangulated when it was created")
+            .into_iter()
+            .flatten()
+            .map(|index| index as u16)
+            .collect();
+        self.get_incomplete_mesh()
+            .with_inserted_indices(Indices::U16(indices))
+    }
+
+    fn get_bounding_box(&self, data: &ShapeData) -> BoundingBox {
+        self.vertex_get_bounding_box(data)
+    }
+
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // like `vertex_get_moment_of_inertia`, but the triangles from the
+        // origin to the edges can be inside out, so their signs are kept
+        let vertices: Vec<_> = self
+            .vertices()
+            .iter()
+            .map(|v| Vec2::from_array(*v).as_dvec2() * size)
+            .collect();
+
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for [v1, v2] in vertices.wrapping_windows::<2>() {
+            let cross = v1.perp_dot(*v2);
+            numerator += cross * (v1.dot(*v1) + v1.dot(*v2) + v2.dot(*v2));
+            denominator += cross;
+        }
+
+        mass * numerator / (6.0 * denominator)
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        self.vertex_get_area(size)
+    }
+
+    fn get_centroid(&self, size: DVec2) -> DVec2 {
+        self.vertex_get_centroid(size)
+    }
+
+    fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
+        if !self.get_bounding_box(data).contains(point) {
+            return false;
+        }
+
+        self.pieces()
+            .any(|(piece, offset)| piece.collides_with_point(&piece_data(data, offset), point))
+    }
+
+    fn collides_with_shape(
+        &self,
+        data: &ShapeData,
+        other_shape: &Shape,
+        other_data: &ShapeData,
+    ) -> Option<CollisionData> {
+        if self.shape_definitely_outside(data, other_shape, other_data) {
+            return None;
+        }
+
+        let collisions: Vec<_> = self
+            .pieces()
+            .filter_map(|(piece, offset)| {
+                Shape::Polygon(piece).collides_with_shape(
+                    &piece_data(data, offset),
+                    other_shape,
+                    other_data,
+                )
+            })
+            .collect();
+        merge_collisions(&collisions)
+    }
+}
+
+impl From<ConcavePolygon> for Mesh {
+    fn from(value: ConcavePolygon) -> Self {
+        value.get_mesh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert_close;
+
+    use super::*;
+    use crate::shapes::validate_vertices;
+
+    const L_SHAPE: [[f32; 2]; 6] = [
+        [0.0, 0.0],
+        [2.0, 0.0],
+        [2.0, 1.0],
+        [1.0, 1.0],
+        [1.0, 2.0],
+        [0.0, 2.0],
+    ];
+
+    fn area(vertices: &[[f32; 2]]) -> f32 {
+        vertices
+            .wrapping_windows::<2>()
+            .map(|[v1, v2]| Vec2::from_array(*v1).perp_dot(Vec2::from_array(*v2)))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_decomposition() {
+        let cup = [
+            [0.0, 0.0],
+            [3.0, 0.0],
+            [3.0, 2.0],
+            [2.0, 2.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ];
+
+        for (vertices, piece_count) in [(&L_SHAPE[..], 2), (&cup[..], 3)] {
+            let polygon = ConcavePolygon::new(vertices).unwrap();
+            assert_eq!(polygon.pieces().count(), piece_count);
+
+            let mut piece_area = 0.0;
+            for (piece, _) in polygon.pieces() {
+                let points: Vec<_> = piece
+                    .vertices()
+                    .iter()
+                    .map(|v| Vec2::from_array(*v))
+                    .collect();
+                assert_eq!(validate_vertices(&points), Ok(()));
+                piece_area += area(piece.vertices());
+            }
+            assert_close!(piece_area, area(vertices), 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_invalid_polygons() {
+        let bowtie = [[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]];
+        let mut clockwise = L_SHAPE;
+        clockwise.reverse();
+
+        assert_eq!(
+            ConcavePolygon::new(&bowtie),
+            Err(PolygonError::SelfIntersecting)
+        );
+        assert_eq!(
+            ConcavePolygon::new(&clockwise),
+            Err(PolygonError::Clockwise)
+        );
+    }
+
+    #[test]
+    fn test_collisions_stay_out_of_the_notch() {
+        let shape = Shape::ConcavePolygon(ConcavePolygon::new(&L_SHAPE).unwrap());
+        // the centroid of the L is at (5/6, 5/6), so this puts the L back
+        // where its vertices say
+        let data = ShapeData {
+            position: DVec2::splat(5.0 / 6.0),
+            rotation: 0.0,
+            size: DVec2::ONE,
+        };
+
+        assert!(shape.collides_with_point(&data, DVec2::new(0.5, 1.5)));
+        assert!(!shape.collides_with_point(&data, DVec2::new(1.5, 1.5)));
+
+        let square_at = |x, y| ShapeData {
+            position: DVec2::new(x, y),
+            rotation: 0.0,
+            size: DVec2::splat(0.8),
+        };
+        for other_shape in [Shape::Square, Shape::Circle, Shape::Pentagon] {
+            // inside the notch, but within the bounding box of the L
+            let notch = square_at(1.5, 1.5);
+            assert!(
+                shape
+                    .collides_with_shape(&data, &other_shape, &notch)
+                    .is_none()
+            );
+            assert!(
+                other_shape
+                    .collides_with_shape(&notch, &shape, &data)
+                    .is_none()
+            );
+        }
+
+        // resting on the short arm, pushed up out of it
+        let collision = shape
+            .collides_with_shape(&data, &Shape::Square, &square_at(1.5, 1.3))
+            .expect("the square overlaps the arm");
+        assert_close!(collision.depth, 0.1, 1e-5);
+        assert_close!(collision.direction.y, -1.0, 1e-5);
+        let collision = Shape::Square
+            .collides_with_shape(&square_at(1.5, 1.3), &shape, &data)
+            .expect("the square overlaps the arm");
+        assert_close!(collision.direction.y, 1.0, 1e-5);
+    }
+
+    #[test]
+    fn test_moment_of_inertia() {
+        let polygon = ConcavePolygon::new(&L_SHAPE).unwrap();
+        let mass = 3.0;
+        let size = DVec2::new(1.5, 0.5);
+
+        // the pieces, moved to their centroids with the parallel axis theorem
+        let total_area = area(polygon.vertices()) as f64;
+        let from_pieces: f64 = polygon
+            .pieces()
+            .map(|(piece, offset)| {
+                let piece_mass = mass * area(piece.vertices()) as f64 / total_area;
+                let offset = offset.as_dvec2() * size;
+                piece.get_moment_of_inertia(piece_mass, size) + piece_mass * offset.length_squared()
+            })
+            .sum();
+        assert_close!(polygon.get_moment_of_inertia(mass, size), from_pieces, 1e-5);
+    }
+}
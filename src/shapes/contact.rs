@@ -0,0 +1,154 @@
+use bevy::math::Vec2;
+
+/// The one or two world-space points where two shapes touch.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ContactPoints {
+    points: [Vec2; 2],
+    count: usize,
+}
+
+impl ContactPoints {
+    pub const fn one(point: Vec2) -> Self {
+        Self {
+            points: [point, Vec2::ZERO],
+            count: 1,
+        }
+    }
+
+    pub const fn two(point1: Vec2, point2: Vec2) -> Self {
+        Self {
+            points: [point1, point2],
+            count: 2,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Vec2] {
+        &self.points[..self.count]
+    }
+}
+
+/// An edge of a polygon, along with the vertex of the edge that is
+/// furthest along some direction.
+#[derive(Debug, Clone, Copy)]
+struct SupportEdge {
+    furthest: Vec2,
+    v1: Vec2,
+    v2: Vec2,
+}
+
+impl SupportEdge {
+    /// Find the edge that is most perpendicular to `direction` out of the
+    /// two edges connected to the vertex furthest along `direction`.
+    fn new(vertices: &[Vec2], direction: Vec2) -> Self {
+        let len = vertices.len();
+        let index = (0..len)
+            .max_by(|&i, &j| {
+                vertices[i]
+                    .dot(direction)
+                    .total_cmp(&vertices[j].dot(direction))
+            })
+            .expect("shape should have vertices");
+
+        let furthest = vertices[index];
+        let previous = vertices[(index + len - 1) % len];
+        let next = vertices[(index + 1) % len];
+
+        let to_previous = (furthest - previous).normalize();
+        let to_next = (furthest - next).normalize();
+        if to_previous.dot(direction).abs() <= to_next.dot(direction).abs() {
+            Self {
+                furthest,
+                v1: previous,
+                v2: furthest,
+            }
+        } else {
+            Self {
+                furthest,
+                v1: furthest,
+                v2: next,
+            }
+        }
+    }
+
+    fn direction(&self) -> Vec2 {
+        (self.v2 - self.v1).normalize()
+    }
+}
+
+/// Remove the parts of the line segment from `p1` to `p2` where
+/// `direction.dot(p) < offset`.
+fn clip_segment(p1: Vec2, p2: Vec2, direction: Vec2, offset: f32) -> Vec<Vec2> {
+    let d1 = direction.dot(p1) - offset;
+    let d2 = direction.dot(p2) - offset;
+
+    let mut points = Vec::with_capacity(2);
+    if d1 >= 0.0 {
+        points.push(p1);
+    }
+    if d2 >= 0.0 {
+        points.push(p2);
+    }
+    if d1 * d2 < 0.0 {
+        points.push(p1 + (p2 - p1) * (d1 / (d1 - d2)));
+    }
+
+    points
+}
+
+/// Find the contact points of two overlapping polygons by clipping the edge
+/// of one polygon against the edge of the other. The vertices must be ordered
+/// counter-clockwise, and `direction` must point from `other_vertices`
+/// towards `self_vertices`.
+pub fn clip_contact_points(
+    self_vertices: &[Vec2],
+    other_vertices: &[Vec2],
+    direction: Vec2,
+) -> ContactPoints {
+    let self_edge = SupportEdge::new(self_vertices, -direction);
+    let other_edge = SupportEdge::new(other_vertices, direction);
+
+    // the reference edge is the one most perpendicular to the collision
+    // direction, and the incident edge gets clipped by it
+    let (reference, incident) = if self_edge.direction().dot(direction).abs()
+        <= other_edge.direction().dot(direction).abs()
+    {
+        (self_edge, other_edge)
+    } else {
+        (other_edge, self_edge)
+    };
+
+    let reference_direction = reference.direction();
+    let clipped = clip_segment(
+        incident.v1,
+        incident.v2,
+        reference_direction,
+        reference_direction.dot(reference.v1),
+    );
+    let [p1, p2] = clipped[..] else {
+        return ContactPoints::one(incident.furthest);
+    };
+
+    let clipped = clip_segment(
+        p1,
+        p2,
+        -reference_direction,
+        -reference_direction.dot(reference.v2),
+    );
+    let [p1, p2] = clipped[..] else {
+        return ContactPoints::one(incident.furthest);
+    };
+
+    // only keep points that are inside the reference shape. As vertices are
+    // ordered counter-clockwise, the outward normal points to the right
+    let reference_normal = Vec2::new(reference_direction.y, -reference_direction.x);
+    let max_depth = reference_normal.dot(reference.v1);
+    match (
+        reference_normal.dot(p1) <= max_depth,
+        reference_normal.dot(p2) <= max_depth,
+    ) {
+        (true, true) => ContactPoints::two(p1, p2),
+        (true, false) => ContactPoints::one(p1),
+        (false, true) => ContactPoints::one(p2),
+        (false, false) => ContactPoints::one(incident.furthest),
+    }
+}
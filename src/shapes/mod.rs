@@ -1,9 +1,19 @@
 mod circle;
+mod compound;
+mod concave;
+mod contact;
 mod ngon;
+mod polygon;
+mod rounded;
 mod spring;
 mod square;
 
 use bevy::math::DVec2;
+pub use compound::{Compound, combined_bounding_box, placed_shapes, update_compound_meshes};
+pub use concave::ConcavePolygon;
+pub use contact::ContactPoints;
+pub use polygon::{Polygon, PolygonError};
+pub use rounded::{RoundedBox, update_rounded_meshes};
 pub use spring::Spring as SpringShape;
 
 use crate::components::{Position, Rotation, Size};
@@ -13,20 +23,28 @@ use bevy::prelude::*;
 use bevy::render::{
     mesh::Mesh, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology,
 };
+use serde::{Deserialize, Serialize};
 use {circle::Circle, ngon::NGon, spring::Spring, square::Square};
 
 #[allow(dead_code)]
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[require(Position, Rotation, Size)]
 pub enum Shape {
     Spring(Spring),
     Circle,
     Square,
+    /// A rectangle with half circles on its shorter sides
+    Capsule,
+    RoundedBox(RoundedBox),
     Triangle,
     Pentagon,
     Hexagon,
     Heptagon,
     Octagon,
+    /// A convex polygon with any vertices
+    Polygon(Polygon),
+    /// A polygon that doesn't need to be convex, such as an L-shaped bracket
+    ConcavePolygon(ConcavePolygon),
 }
 
 impl Shape {
@@ -35,11 +53,35 @@ impl Shape {
             Self::Spring(spring) => spring,
             Self::Circle => &Circle,
             Self::Square => &Square,
+            Self::Capsule => &RoundedBox::CAPSULE,
+            Self::RoundedBox(rounded_box) => rounded_box,
             Self::Triangle => &NGon::<3>,
             Self::Pentagon => &NGon::<5>,
             Self::Hexagon => &NGon::<6>,
             Self::Heptagon => &NGon::<7>,
             Self::Octagon => &NGon::<8>,
+            Self::Polygon(polygon) => polygon,
+            Self::ConcavePolygon(polygon) => polygon,
+        }
+    }
+
+    /// A mesh of the shape with the given size. Only rounded shapes need to
+    /// know their size, the others are stretched to fit.
+    pub fn get_sized_mesh(&self, size: DVec2) -> Mesh {
+        match self {
+            Self::Capsule => RoundedBox::CAPSULE.get_sized_mesh(size),
+            Self::RoundedBox(rounded_box) => rounded_box.get_sized_mesh(size),
+            _ => self.get_mesh(),
+        }
+    }
+
+    /// Shapes only know how to collide with shapes of the same or a lower
+    /// level, so collisions are handled by the shape with the higher level
+    const fn collision_level(&self) -> u8 {
+        match self {
+            Self::ConcavePolygon(_) => 2,
+            Self::Capsule | Self::RoundedBox(_) => 1,
+            _ => 0,
         }
     }
 }
@@ -71,12 +113,23 @@ impl From<(Position, Size, Rotation)> for ShapeData {
 pub struct CollisionData {
     pub depth: f32,
     pub direction: Vec2,
+    pub contact_points: ContactPoints,
 }
 
-#[allow(dead_code)]
 impl CollisionData {
     fn new(depth: f32, direction: Vec2) -> Self {
-        Self { depth, direction }
+        Self {
+            depth,
+            direction,
+            contact_points: ContactPoints::default(),
+        }
+    }
+
+    const fn with_contact_points(self, contact_points: ContactPoints) -> Self {
+        Self {
+            contact_points,
+            ..self
+        }
     }
 }
 
@@ -89,10 +142,26 @@ impl ShapeImpl for Shape {
         self.get_shape().get_mesh()
     }
 
+    fn get_shape_vertices(&self, data: &ShapeData) -> Vec<Vec2> {
+        self.get_shape().get_shape_vertices(data)
+    }
+
     fn get_bounding_box(&self, data: &ShapeData) -> BoundingBox {
         self.get_shape().get_bounding_box(data)
     }
 
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        self.get_shape().get_moment_of_inertia(mass, size)
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        self.get_shape().get_area(size)
+    }
+
+    fn get_centroid(&self, size: DVec2) -> DVec2 {
+        self.get_shape().get_centroid(size)
+    }
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
         self.get_shape().collides_with_point(data, point)
     }
@@ -103,6 +172,17 @@ impl ShapeImpl for Shape {
         other_shape: &Self,
         other_data: &ShapeData,
     ) -> Option<CollisionData> {
+        if other_shape.collision_level() > self.collision_level() {
+            // let the other shape handle the collision. Doing this requires
+            // us to flip the collision direction
+            return other_shape
+                .collides_with_shape(other_data, self, data)
+                .map(|collision_data| CollisionData {
+                    direction: -collision_data.direction,
+                    ..collision_data
+                });
+        }
+
         self.get_shape()
             .collides_with_shape(data, other_shape, other_data)
     }
@@ -115,6 +195,16 @@ pub trait ShapeImpl {
 
     fn get_bounding_box(&self, data: &ShapeData) -> BoundingBox;
 
+    /// Moment of inertia around the center of the shape, assuming the
+    /// mass is evenly distributed.
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64;
+
+    /// Area of the shape with the given size
+    fn get_area(&self, size: DVec2) -> f64;
+
+    /// Center of the area of the shape, relative to its position
+    fn get_centroid(&self, size: DVec2) -> DVec2;
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool;
 
     fn collides_with_shape(
@@ -126,31 +216,7 @@ pub trait ShapeImpl {
 
     /// Create `Mesh` with position, uv, and normals, but not indices.
     fn get_incomplete_mesh(&self) -> Mesh {
-        let vertices = self.get_vertices();
-
-        Mesh::new(
-            PrimitiveTopology::TriangleList,
-            RenderAssetUsages::RENDER_WORLD,
-        )
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            vertices
-                .iter()
-                .map(|pos| [pos[0], pos[1], 0.0])
-                .collect::<Vec<[f32; 3]>>(),
-        )
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            // vertices are in [-0.5, 0.5], transform them to be [0, 1]
-            vertices
-                .iter()
-                .map(|pos| [pos[0] + 0.5, pos[1] + 0.5])
-                .collect::<Vec<[f32; 2]>>(),
-        )
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            [[0.0, 0.0, 1.0]].repeat(vertices.len()),
-        )
+        incomplete_mesh(&self.get_vertices())
     }
 
     fn get_shape_vertices(&self, data: &ShapeData) -> Vec<Vec2> {
@@ -216,6 +282,58 @@ pub trait ShapeImpl {
         BoundingBox::from_corners(top_right.as_dvec2(), bottom_left.as_dvec2())
     }
 
+    /// Get moment of inertia by splitting the shape into triangles with one
+    /// corner at the origin. Note: This assumes the center of mass is at the origin.
+    fn vertex_get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        let vertices: Vec<_> = self
+            .get_vertices()
+            .iter()
+            .map(|v| Vec2::from_array(*v).as_dvec2() * size)
+            .collect();
+
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for [v1, v2] in vertices.wrapping_windows::<2>() {
+            let cross = v1.perp_dot(*v2).abs();
+            numerator += cross * (v1.dot(*v1) + v1.dot(*v2) + v2.dot(*v2));
+            denominator += cross;
+        }
+
+        mass * numerator / (6.0 * denominator)
+    }
+
+    /// Get the area with the shoelace formula. Vertices must be ordered
+    /// counter-clockwise, but the shape doesn't need to be convex.
+    fn vertex_get_area(&self, size: DVec2) -> f64 {
+        let vertices = self.get_vertices();
+        let mut area = 0.0;
+        for [v1, v2] in vertices.wrapping_windows::<2>() {
+            area += Vec2::from_array(*v1)
+                .as_dvec2()
+                .perp_dot(Vec2::from_array(*v2).as_dvec2());
+        }
+        area * size.x * size.y / 2.0
+    }
+
+    /// Get the centroid from the triangles between the origin and each edge.
+    /// Like `vertex_get_area`, the shape doesn't need to be convex.
+    fn vertex_get_centroid(&self, size: DVec2) -> DVec2 {
+        let vertices: Vec<_> = self
+            .get_vertices()
+            .iter()
+            .map(|v| Vec2::from_array(*v).as_dvec2() * size)
+            .collect();
+
+        let mut area = 0.0;
+        let mut weighted_sum = DVec2::ZERO;
+        for [v1, v2] in vertices.wrapping_windows::<2>() {
+            let cross = v1.perp_dot(*v2);
+            area += cross;
+            weighted_sum += cross * (*v1 + *v2);
+        }
+        weighted_sum / (3.0 * area)
+    }
+
     /// See if point is inside shape by iterating over all vertices.
     /// Note: This assumes shape is convex and vertices are ordered counter-clockwise
     fn vertex_collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
@@ -284,13 +402,40 @@ pub trait ShapeImpl {
             collision_direction = -collision_direction;
         }
 
-        Some(CollisionData {
-            depth: min_depth,
-            direction: collision_direction,
-        })
+        let contact_points =
+            contact::clip_contact_points(&self_vertices, &other_vertices, collision_direction);
+
+        Some(CollisionData::new(min_depth, collision_direction).with_contact_points(contact_points))
     }
 }
 
+/// Create `Mesh` with position, uv, and normals, but not indices.
+fn incomplete_mesh(vertices: &[[f32; 2]]) -> Mesh {
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vertices
+            .iter()
+            .map(|pos| [pos[0], pos[1], 0.0])
+            .collect::<Vec<[f32; 3]>>(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        // vertices are in [-0.5, 0.5], transform them to be [0, 1]
+        vertices
+            .iter()
+            .map(|pos| [pos[0] + 0.5, pos[1] + 0.5])
+            .collect::<Vec<[f32; 2]>>(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        [[0.0, 0.0, 1.0]].repeat(vertices.len()),
+    )
+}
+
 /// Transform a point relative to some object with a position, size,
 /// and rotation such that the position is effectively (0, 0), the size
 /// is effectively (1, 1), and the rotation is effectively 0.
@@ -303,31 +448,41 @@ pub fn transform_point(data: &ShapeData, point: DVec2) -> DVec2 {
     point / data.size
 }
 
-#[cfg(debug_assertions)]
-/// Assert that vertices define a shape that is convex and ordered counter-clockwise
-pub fn check_vertices(vertices: &[Vec2]) {
-    use core::f32::consts::PI;
+/// Check that vertices define a shape that is convex and ordered counter-clockwise
+pub fn validate_vertices(vertices: &[Vec2]) -> Result<(), PolygonError> {
+    use core::f32::consts::TAU;
+
+    // every corner of a convex shape turns left when going counter-clockwise
+    let turns: Vec<_> = vertices
+        .wrapping_windows::<3>()
+        .map(|[v1, v2, v3]| (v2 - v1).perp_dot(v3 - v2))
+        .collect();
+    if turns.iter().all(|turn| *turn < 0.0) {
+        return Err(PolygonError::Clockwise);
+    }
+    if !turns.iter().all(|turn| *turn > 0.0) {
+        return Err(PolygonError::NotConvex);
+    }
 
+    // a star also turns left at every corner, but goes around more than once
     let center = vertices.iter().sum::<Vec2>() / vertices.len() as f32;
     let origin_vertices: Vec<_> = vertices.iter().map(|vertex| vertex - center).collect();
-
-    // Assert that shape is convex
-    for [v1, v2, v3] in origin_vertices.wrapping_windows::<3>() {
-        let to_v1 = v1 - v2;
-        let to_v3 = v3 - v2;
-        assert!(
-            to_v1.angle_to(to_v3) < PI,
-            "To use vertex based collision, shape must be convex",
-        );
+    let winding: f32 = origin_vertices
+        .wrapping_windows::<2>()
+        .map(|[v1, v2]| v1.angle_to(*v2))
+        .sum();
+    if (winding - TAU).abs() > 1e-3 {
+        return Err(PolygonError::NotConvex);
     }
 
-    // Assert that vertices are ordered counter-clockwise
-    for [v1, v2] in origin_vertices.wrapping_windows::<2>() {
-        assert!(
-            v1.angle_to(*v2) > 0.0,
-            "To use vertex based collision, \
-            vertices must be ordered counter-clockwise",
-        );
+    Ok(())
+}
+
+#[cfg(debug_assertions)]
+/// Assert that vertices define a shape that is convex and ordered counter-clockwise
+pub fn check_vertices(vertices: &[Vec2]) {
+    if let Err(err) = validate_vertices(vertices) {
+        panic!("To use vertex based collision, {err}");
     }
 }
 
@@ -432,4 +587,163 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_vertex_get_moment_of_inertia() {
+        let mass = 2.5;
+
+        for size in [DVec2::ONE, DVec2::new(2.0, 0.5), DVec2::new(0.1, 3.0)] {
+            // vertex based moment of inertia should be exact for a square
+            let exact = Shape::Square.get_moment_of_inertia(mass, size);
+            let from_vertices = Square.vertex_get_moment_of_inertia(mass, size);
+            assert_close!(from_vertices, exact, 1e-10);
+
+            // and close for a circle
+            let exact = Shape::Circle.get_moment_of_inertia(mass, size);
+            let from_vertices = NGon::<30>.vertex_get_moment_of_inertia(mass, size);
+            assert_close!(from_vertices, exact, 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_area_and_centroid() {
+        let wedge = Polygon::new(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]).unwrap();
+
+        for size in [DVec2::ONE, DVec2::new(2.0, 0.5), DVec2::new(0.1, 3.0)] {
+            // the exact areas agree with the shoelace formula
+            assert_close!(Square.get_area(size), Square.vertex_get_area(size), 1e-10);
+            assert_close!(
+                NGon::<5>.get_area(size),
+                NGon::<5>.vertex_get_area(size),
+                1e-6
+            );
+            assert_close!(
+                Shape::Polygon(wedge).get_area(size),
+                0.5 * size.x * size.y,
+                1e-6
+            );
+            // and a circle is close to a polygon with many sides
+            assert_close!(
+                Shape::Circle.get_area(size),
+                NGon::<30>.vertex_get_area(size),
+                1e-2
+            );
+            // square corners make a rectangle
+            assert_close!(
+                Shape::RoundedBox(RoundedBox { corner_radius: 0.0 }).get_area(size),
+                size.x * size.y,
+                1e-10
+            );
+
+            // shapes are centered on their centroid
+            for shape in [Shape::Triangle, Shape::Polygon(wedge)] {
+                assert!(shape.get_centroid(size).length() < 1e-6);
+            }
+            assert!(NGon::<3>.vertex_get_centroid(size).length() < 1e-6);
+        }
+
+        // a capsule as wide as it is tall is a circle
+        assert_close!(
+            Shape::Capsule.get_area(DVec2::ONE),
+            Shape::Circle.get_area(DVec2::ONE),
+            1e-10
+        );
+    }
+
+    #[test]
+    fn test_contact_points() {
+        let data1 = ShapeData {
+            position: DVec2::ZERO,
+            rotation: 0.0,
+            size: DVec2::ONE,
+        };
+
+        let flat_data = ShapeData {
+            position: DVec2::new(0.2, 0.9),
+            rotation: 0.0,
+            size: DVec2::ONE,
+        };
+        let tilted_data = ShapeData {
+            position: DVec2::new(0.0, 0.5 + 0.5 * 2f64.sqrt() - 0.05),
+            rotation: PI / 4.0,
+            size: DVec2::ONE,
+        };
+
+        for (other_data, expected_points) in [
+            (flat_data, vec![Vec2::new(0.5, 0.4), Vec2::new(-0.3, 0.4)]),
+            (tilted_data, vec![Vec2::new(0.0, 0.45)]),
+        ] {
+            let collision_data = Shape::Square
+                .collides_with_shape(&data1, &Shape::Square, &other_data)
+                .expect("shapes should collide");
+
+            let got_points = collision_data.contact_points.as_slice();
+            assert_eq!(got_points.len(), expected_points.len());
+            for expected in expected_points {
+                assert!(
+                    got_points.iter().any(|got| got.distance(expected) < 1e-5),
+                    "{expected} not in {got_points:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_polygon_validation() {
+        let wedge = Polygon::new(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]).unwrap();
+        // the centroid is moved to the origin
+        assert_close!(wedge.vertices()[0][0], -1.0 / 3.0, 1e-6);
+        assert_close!(wedge.vertices()[2][1], 2.0 / 3.0, 1e-6);
+        // which doesn't change a polygon that is already centered
+        assert_eq!(Polygon::new(wedge.vertices()), Ok(wedge));
+
+        let pentagram: Vec<_> = [0, 2, 4, 1, 3]
+            .map(|i| NGon::<5>.get_vertices()[i])
+            .to_vec();
+        for (vertices, error) in [
+            (vec![[0.0, 0.0], [1.0, 0.0]], PolygonError::TooFewVertices),
+            (
+                vec![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]],
+                PolygonError::Clockwise,
+            ),
+            (
+                vec![
+                    [-1.0, -1.0],
+                    [1.0, -1.0],
+                    [1.0, 1.0],
+                    [0.0, 0.0],
+                    [-1.0, 1.0],
+                ],
+                PolygonError::NotConvex,
+            ),
+            (pentagram, PolygonError::NotConvex),
+            (NGon::<17>.get_vertices(), PolygonError::TooManyVertices),
+        ] {
+            assert_eq!(Polygon::new(&vertices), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_polygon_collides_like_square() {
+        let square = Shape::Polygon(Polygon::new(&Square.get_vertices()).unwrap());
+        let data = ShapeData {
+            position: DVec2::ZERO,
+            rotation: PI / 5.0,
+            size: DVec2::new(2.0, 1.0),
+        };
+
+        for (x, y) in [(0.3, 1.1), (1.4, -0.2), (-0.9, 0.9), (3.0, 0.0)] {
+            let other_data = ShapeData {
+                position: DVec2::new(x, y),
+                rotation: -PI / 6.0,
+                size: DVec2::new(1.0, 2.0),
+            };
+            for other_shape in [Shape::Square, Shape::Pentagon, Shape::Circle] {
+                let expected = Shape::Square.collides_with_shape(&data, &other_shape, &other_data);
+                let got = square.collides_with_shape(&data, &other_shape, &other_data);
+                assert_eq!(got.map(|got| got.depth), expected.map(|e| e.depth));
+                assert_eq!(got.map(|got| got.direction), expected.map(|e| e.direction));
+            }
+        }
+    }
 }
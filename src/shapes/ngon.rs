@@ -49,6 +49,23 @@ impl<const N: u8> ShapeImpl for NGon<N> {
         }
     }
 
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // NGons are centered at the origin, so this function is safe to use
+        self.vertex_get_moment_of_inertia(mass, size)
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        // N triangles between the center and the edges, each with two sides
+        // as long as the radius of 0.5, stretched by the size
+        let angle = 2.0 * std::f64::consts::PI / f64::from(N);
+        f64::from(N) * angle.sin() / 8.0 * size.x * size.y
+    }
+
+    fn get_centroid(&self, _size: DVec2) -> DVec2 {
+        // the vertices are spread evenly around the origin
+        DVec2::ZERO
+    }
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
         if self.point_definitely_outside(data, point) {
             return false;
@@ -0,0 +1,211 @@
+use std::{error, fmt};
+
+use bevy::math::{DVec2, Vec2};
+use bevy::render::mesh::{Indices, Mesh};
+use serde::{Deserialize, Serialize};
+
+use super::{CollisionData, Shape, ShapeData, ShapeImpl, validate_vertices};
+use crate::utils::{BoundingBox, WrappingWindows};
+
+/// The most vertices a polygon can have. Shapes are stored inline, so this
+/// keeps `Shape` small enough to copy.
+pub const MAX_VERTICES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonError {
+    TooFewVertices,
+    TooManyVertices,
+    NotConvex,
+    Clockwise,
+    SelfIntersecting,
+}
+
+impl fmt::Display for PolygonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooFewVertices => f.write_str("a polygon needs at least 3 vertices"),
+            Self::TooManyVertices => {
+                write!(f, "a polygon can have at most {MAX_VERTICES} vertices")
+            }
+            Self::NotConvex => f.write_str("shape must be convex"),
+            Self::Clockwise => f.write_str("vertices must be ordered counter-clockwise"),
+            Self::SelfIntersecting => f.write_str("edges must not cross or overlap"),
+        }
+    }
+}
+
+impl error::Error for PolygonError {}
+
+/// A convex polygon with vertices ordered counter-clockwise. Like the other
+/// shapes, the vertices are scaled by the `Size` of the entity, so with a size
+/// of (1, 1) they are offsets from its position.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<[f32; 2]>", into = "Vec<[f32; 2]>")]
+pub struct Polygon {
+    vertices: [[f32; 2]; MAX_VERTICES],
+    len: u8,
+}
+
+impl Polygon {
+    /// Create a polygon, moving the vertices so their centroid is at the
+    /// origin. The position of a body is its center of mass, which is where
+    /// the centroid of a uniform polygon is.
+    pub fn new(vertices: &[[f32; 2]]) -> Result<Self, PolygonError> {
+        if vertices.len() < 3 {
+            return Err(PolygonError::TooFewVertices);
+        }
+        if vertices.len() > MAX_VERTICES {
+            return Err(PolygonError::TooManyVertices);
+        }
+
+        let points: Vec<_> = vertices.iter().map(|v| Vec2::from_array(*v)).collect();
+        validate_vertices(&points)?;
+
+        Ok(Self::from_convex(&points).0)
+    }
+
+    /// Create a polygon from vertices that are known to be valid, and return
+    /// it along with how far it was moved to center it
+    pub(super) fn from_convex(points: &[Vec2]) -> (Self, Vec2) {
+        let mut points = points.to_vec();
+        let offset = center_on_centroid(&mut points);
+
+        let mut polygon = Self {
+            vertices: [[0.0; 2]; MAX_VERTICES],
+            len: points.len() as u8,
+        };
+        for (vertex, point) in polygon.vertices.iter_mut().zip(points) {
+            *vertex = point.to_array();
+        }
+        (polygon, offset)
+    }
+
+    pub fn vertices(&self) -> &[[f32; 2]] {
+        &self.vertices[..self.len as usize]
+    }
+}
+
+/// Move the vertices of a polygon so its centroid is at the origin, and return
+/// the centroid. Polygons that are already centered are left alone, so saving
+/// and loading one gives back the same vertices.
+pub(super) fn center_on_centroid(points: &mut [Vec2]) -> Vec2 {
+    let centroid = centroid(points);
+    let extent = points
+        .iter()
+        .map(|point| point.abs().max_element())
+        .fold(0.0, f32::max);
+    if centroid.length() <= f32::EPSILON * extent {
+        return Vec2::ZERO;
+    }
+
+    for point in points.iter_mut() {
+        *point -= centroid;
+    }
+    centroid
+}
+
+/// The center of the area of a polygon, which doesn't need to be convex
+fn centroid(vertices: &[Vec2]) -> Vec2 {
+    let mut area = 0.0;
+    let mut weighted_sum = DVec2::ZERO;
+    for [v1, v2] in vertices.wrapping_windows::<2>() {
+        let (v1, v2) = (v1.as_dvec2(), v2.as_dvec2());
+        let cross = v1.perp_dot(v2);
+        area += cross;
+        weighted_sum += cross * (v1 + v2);
+    }
+    (weighted_sum / (3.0 * area)).as_vec2()
+}
+
+impl TryFrom<Vec<[f32; 2]>> for Polygon {
+    type Error = PolygonError;
+
+    fn try_from(value: Vec<[f32; 2]>) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<Polygon> for Vec<[f32; 2]> {
+    fn from(value: Polygon) -> Self {
+        value.vertices().to_vec()
+    }
+}
+
+impl ShapeImpl for Polygon {
+    fn get_vertices(&self) -> Vec<[f32; 2]> {
+        self.vertices().to_vec()
+    }
+
+    fn get_mesh(&self) -> Mesh {
+        // convex polygons can be split into a fan of triangles
+        let mut indices = Vec::with_capacity(3 * (self.len as usize - 2));
+        for i in 0..(self.len as u16 - 2) {
+            indices.push(0);
+            indices.push(i + 1);
+            indices.push(i + 2);
+        }
+        self.get_incomplete_mesh()
+            .with_inserted_indices(Indices::U16(indices))
+    }
+
+    fn get_bounding_box(&self, data: &ShapeData) -> BoundingBox {
+        self.vertex_get_bounding_box(data)
+    }
+
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // the vertices are centered on the centroid, so this function is safe to use
+        self.vertex_get_moment_of_inertia(mass, size)
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        self.vertex_get_area(size)
+    }
+
+    fn get_centroid(&self, size: DVec2) -> DVec2 {
+        // this is the origin, as the vertices were centered when the polygon
+        // was created
+        self.vertex_get_centroid(size)
+    }
+
+    fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
+        // the vertices can reach outside the unit square, so the usual quick
+        // check doesn't work
+        if !self.get_bounding_box(data).contains(point) {
+            return false;
+        }
+
+        // the vertices were checked when the polygon was created, so this
+        // function is safe to use
+        self.vertex_collides_with_point(data, point)
+    }
+
+    fn collides_with_shape(
+        &self,
+        data: &ShapeData,
+        other_shape: &Shape,
+        other_data: &ShapeData,
+    ) -> Option<CollisionData> {
+        if matches!(other_shape, Shape::Circle) {
+            // let the circle handle the collision. Doing this requires us
+            // to flip the collision direction
+            return other_shape
+                .collides_with_shape(other_data, &Shape::Polygon(*self), data)
+                .map(|collision_data| CollisionData {
+                    direction: -collision_data.direction,
+                    ..collision_data
+                });
+        }
+
+        if self.shape_definitely_outside(data, other_shape, other_data) {
+            return None;
+        }
+
+        self.vertex_collides_with_shape(data, other_shape, other_data)
+    }
+}
+
+impl From<Polygon> for Mesh {
+    fn from(value: Polygon) -> Self {
+        value.get_mesh()
+    }
+}
@@ -0,0 +1,573 @@
+use std::f64::consts::PI;
+
+use bevy::math::{DVec2, Vec2};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, Mesh};
+use serde::{Deserialize, Serialize};
+
+use super::circle::Circle;
+use super::{CollisionData, ContactPoints, Shape, ShapeData, ShapeImpl, incomplete_mesh};
+use crate::components::Size;
+use crate::utils::{BoundingBox, ShapeProjection, WrappingWindows};
+
+/// Points on each rounded corner of the outline, not counting the first
+const ARC_SEGMENTS: u32 = 8;
+/// Pairs of points that are this much further apart than the closest pair
+/// still touch, so that flat sides resting on each other get two contact points
+const CONTACT_TOLERANCE: f32 = 1e-3;
+
+/// A rectangle with rounded corners. The radius of the corners is a fraction
+/// of half the shorter side, so a radius of 0 is a square and a radius of 1 is
+/// a capsule.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RoundedBox {
+    pub corner_radius: f64,
+}
+
+impl RoundedBox {
+    pub const CAPSULE: Self = Self { corner_radius: 1.0 };
+
+    /// Radius of the corners of a box with the given size
+    fn radius(&self, size: DVec2) -> f64 {
+        self.corner_radius.clamp(0.0, 1.0) * size.min_element() / 2.0
+    }
+
+    /// The outline, counter-clockwise from the bottom of the right side, of a
+    /// box with the given size
+    fn outline(&self, size: DVec2) -> Vec<DVec2> {
+        let radius = self.radius(size);
+        let half = size / 2.0 - radius;
+        let corners = [
+            (DVec2::new(half.x, -half.y), -PI / 2.0),
+            (half, 0.0),
+            (DVec2::new(-half.x, half.y), PI / 2.0),
+            (-half, PI),
+        ];
+
+        let mut points = Vec::with_capacity(4 * (ARC_SEGMENTS as usize + 1));
+        for (corner, start) in corners {
+            for i in 0..=ARC_SEGMENTS {
+                let angle = start + PI / 2.0 * f64::from(i) / f64::from(ARC_SEGMENTS);
+                points.push(corner + radius * DVec2::from_angle(angle));
+            }
+        }
+
+        // tiny corners collapse into a single point, as vertices that are too
+        // close together make the edges between them point anywhere
+        let tolerance = 1e-4 * size.max_element();
+        points.dedup_by(|a, b| a.distance(*b) < tolerance);
+        if points.len() > 1 && points[0].distance(points[points.len() - 1]) < tolerance {
+            points.pop();
+        }
+        points
+    }
+
+    /// A mesh of the box with the given size. Meshes are stretched to the size
+    /// of the entity, so the corners are only round if the mesh was made for
+    /// that size.
+    pub fn get_sized_mesh(&self, size: DVec2) -> Mesh {
+        let vertices: Vec<_> = self
+            .outline(size)
+            .iter()
+            .map(|point| (*point / size).as_vec2().to_array())
+            .collect();
+        let indices = (1..vertices.len() as u16 - 1)
+            .flat_map(|i| [0, i, i + 1])
+            .collect();
+        incomplete_mesh(&vertices).with_inserted_indices(Indices::U16(indices))
+    }
+
+    fn core(&self, data: &ShapeData) -> Core {
+        let radius = self.radius(data.size);
+        let half = (data.size / 2.0 - radius).as_vec2();
+        let mut vertices = vec![
+            Vec2::new(-half.x, -half.y),
+            Vec2::new(half.x, -half.y),
+            half,
+            Vec2::new(-half.x, half.y),
+        ];
+        vertices.dedup();
+        if vertices.len() > 1 && vertices[0] == vertices[vertices.len() - 1] {
+            vertices.pop();
+        }
+
+        let rotation = Vec2::from_angle(data.rotation as f32);
+        let position = data.position.as_vec2();
+        Core {
+            vertices: vertices
+                .iter()
+                .map(|vertex| position + rotation.rotate(*vertex))
+                .collect(),
+            radius: radius as f32,
+            center: position,
+        }
+    }
+}
+
+/// A convex polygon, line segment or point, grown by a radius. Every shape
+/// except ellipses and concave polygons is one of these, which lets them all
+/// collide with each other exactly in the same way.
+///
+/// Ellipses aren't exact: the distance between an ellipse and a rounded
+/// corner has no closed form, so they collide with the outline of rounded
+/// shapes instead. The outline cuts the corners by at most `r(1 - cos(π/32))`,
+/// and only the directions of its edges are tried as separating axes, so the
+/// depth of a collision with an ellipse can be off by about 1% of the corner
+/// radius `r`. Flat sides are exact.
+struct Core {
+    vertices: Vec<Vec2>,
+    radius: f32,
+    center: Vec2,
+}
+
+impl Core {
+    fn of(shape: &Shape, data: &ShapeData) -> Option<Self> {
+        match shape {
+            Shape::Capsule => Some(RoundedBox::CAPSULE.core(data)),
+            Shape::RoundedBox(rounded_box) => Some(rounded_box.core(data)),
+            Shape::Circle if Circle::is_circular(data) => Some(Self {
+                vertices: vec![data.position.as_vec2()],
+                radius: 0.5 * data.size.x as f32,
+                center: data.position.as_vec2(),
+            }),
+            Shape::Circle | Shape::ConcavePolygon(_) => None,
+            // springs collide as the square around their coils
+            Shape::Spring(_) => Some(Self {
+                vertices: Shape::Square.get_shape_vertices(data),
+                radius: 0.0,
+                center: data.position.as_vec2(),
+            }),
+            _ => Some(Self {
+                vertices: shape.get_shape_vertices(data),
+                radius: 0.0,
+                center: data.position.as_vec2(),
+            }),
+        }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        self.vertices
+            .wrapping_windows::<2>()
+            .map(|[v1, v2]| (*v1, *v2))
+    }
+
+    /// The directions that can separate this core from another one
+    fn axes(&self) -> Vec<Vec2> {
+        match self.vertices[..] {
+            [_] => Vec::new(),
+            [v1, v2] => {
+                let direction = (v2 - v1).normalize();
+                vec![direction, direction.perp()]
+            }
+            _ => self
+                .edges()
+                .map(|(v1, v2)| (v2 - v1).perp().normalize())
+                .collect(),
+        }
+    }
+}
+
+fn closest_on_segment(point: Vec2, (v1, v2): (Vec2, Vec2)) -> Vec2 {
+    let edge = v2 - v1;
+    if edge == Vec2::ZERO {
+        return v1;
+    }
+    let t = (point - v1).dot(edge) / edge.length_squared();
+    v1 + t.clamp(0.0, 1.0) * edge
+}
+
+/// Keep the two points furthest apart along `tangent`
+fn extreme_points(points: &[Vec2], tangent: Vec2) -> ContactPoints {
+    let along = |a: &&Vec2, b: &&Vec2| a.dot(tangent).total_cmp(&b.dot(tangent));
+    let first = points
+        .iter()
+        .min_by(along)
+        .expect("there should be a point");
+    let last = points
+        .iter()
+        .max_by(along)
+        .expect("there should be a point");
+    if first.distance(*last) < CONTACT_TOLERANCE {
+        ContactPoints::one(*first)
+    } else {
+        ContactPoints::two(*first, *last)
+    }
+}
+
+/// The smallest overlap of the cores along any axis, or `None` if an axis
+/// separates them
+fn core_overlap(a: &Core, b: &Core) -> Option<(f32, Vec2)> {
+    let mut min_overlap = f32::INFINITY;
+    let mut axis = Vec2::ZERO;
+    for candidate in a.axes().into_iter().chain(b.axes()) {
+        let overlap = ShapeProjection::project_vertices(&a.vertices, candidate)
+            .overlap(&ShapeProjection::project_vertices(&b.vertices, candidate));
+        if overlap < 0.0 {
+            return None;
+        }
+        if overlap < min_overlap {
+            min_overlap = overlap;
+            axis = candidate;
+        }
+    }
+    // two points have no axes, so they can only be separate
+    min_overlap.is_finite().then_some((min_overlap, axis))
+}
+
+/// Collide two cores. The direction points towards `a`.
+fn collide_cores(a: &Core, b: &Core) -> Option<CollisionData> {
+    let radius = a.radius + b.radius;
+
+    if let Some((overlap, axis)) = core_overlap(a, b) {
+        // the cores themselves overlap, so push them apart along the axis
+        // where they overlap the least
+        let direction = if axis.dot(a.center - b.center) < 0.0 {
+            -axis
+        } else {
+            axis
+        };
+        let furthest = b
+            .vertices
+            .iter()
+            .map(|vertex| vertex.dot(direction))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let deepest: Vec<_> = b
+            .vertices
+            .iter()
+            .filter(|vertex| vertex.dot(direction) > furthest - CONTACT_TOLERANCE)
+            .map(|vertex| *vertex + b.radius * direction)
+            .collect();
+        return Some(
+            CollisionData::new(overlap + radius, direction)
+                .with_contact_points(extreme_points(&deepest, direction.perp())),
+        );
+    }
+
+    // otherwise the closest points are on a vertex of one core and an edge
+    // of the other
+    let mut pairs: Vec<_> = a
+        .vertices
+        .iter()
+        .flat_map(|vertex| {
+            b.edges()
+                .map(|edge| (*vertex, closest_on_segment(*vertex, edge)))
+        })
+        .chain(b.vertices.iter().flat_map(|vertex| {
+            a.edges()
+                .map(|edge| (closest_on_segment(*vertex, edge), *vertex))
+        }))
+        .collect();
+    let distance = |(point_a, point_b): &(Vec2, Vec2)| point_a.distance(*point_b);
+    let (closest_a, closest_b) = *pairs
+        .iter()
+        .min_by(|pair1, pair2| distance(pair1).total_cmp(&distance(pair2)))
+        .expect("cores should have vertices");
+    let min_distance = closest_a.distance(closest_b);
+    if min_distance >= radius {
+        return None;
+    }
+
+    let direction = (closest_a - closest_b).normalize_or(Vec2::Y);
+    pairs.retain(|pair| distance(pair) < min_distance + CONTACT_TOLERANCE);
+    // halfway between the surfaces
+    let points: Vec<_> = pairs
+        .iter()
+        .map(|(point_a, point_b)| {
+            0.5 * (*point_a - a.radius * direction + *point_b + b.radius * direction)
+        })
+        .collect();
+    Some(
+        CollisionData::new(radius - min_distance, direction)
+            .with_contact_points(extreme_points(&points, direction.perp())),
+    )
+}
+
+impl ShapeImpl for RoundedBox {
+    fn get_vertices(&self) -> Vec<[f32; 2]> {
+        self.outline(DVec2::ONE)
+            .iter()
+            .map(|point| point.as_vec2().to_array())
+            .collect()
+    }
+
+    fn get_mesh(&self) -> Mesh {
+        self.get_sized_mesh(DVec2::ONE)
+    }
+
+    fn get_shape_vertices(&self, data: &ShapeData) -> Vec<Vec2> {
+        // the corners are only round for the actual size
+        self.outline(data.size)
+            .iter()
+            .map(|point| {
+                (data.position + DVec2::from_angle(data.rotation).rotate(*point)).as_vec2()
+            })
+            .collect()
+    }
+
+    fn get_bounding_box(&self, data: &ShapeData) -> BoundingBox {
+        let core = self.core(data);
+        let mut top_right = core.vertices[0];
+        let mut bottom_left = core.vertices[0];
+        for vertex in &core.vertices[1..] {
+            top_right = top_right.max(*vertex);
+            bottom_left = bottom_left.min(*vertex);
+        }
+
+        BoundingBox::from_corners(
+            (top_right + core.radius).as_dvec2(),
+            (bottom_left - core.radius).as_dvec2(),
+        )
+    }
+
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // the box is split into a band across the full width, two strips
+        // above and below it, and a quarter of a circle in each corner
+        let radius = self.radius(size);
+        let corner = size / 2.0 - radius;
+        let area = self.get_area(size);
+        let rectangle = |width: f64, height: f64, offset: f64| {
+            width * height * ((width * width + height * height) / 12.0 + offset * offset)
+        };
+
+        let band = rectangle(size.x, size.y - 2.0 * radius, 0.0);
+        let strips = 2.0 * rectangle(size.x - 2.0 * radius, radius, corner.y + radius / 2.0);
+        let quarter_area = PI * radius * radius / 4.0;
+        // the center of mass of each quarter is 4r/3π further out than its corner
+        let quarters = 4.0
+            * quarter_area
+            * (radius * radius / 2.0
+                + corner.length_squared()
+                + 2.0 * 4.0 * radius / (3.0 * PI) * (corner.x + corner.y));
+
+        mass * (band + strips + quarters) / area
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        // a rectangle without the parts of its corners outside the quarter circles
+        let radius = self.radius(size);
+        size.x * size.y - (4.0 - PI) * radius * radius
+    }
+
+    fn get_centroid(&self, _size: DVec2) -> DVec2 {
+        DVec2::ZERO
+    }
+
+    fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
+        let radius = self.radius(data.size);
+        let half = data.size / 2.0 - radius;
+        let local = DVec2::from_angle(-data.rotation).rotate(point - data.position);
+        local.distance_squared(local.clamp(-half, half)) <= radius * radius
+    }
+
+    fn collides_with_shape(
+        &self,
+        data: &ShapeData,
+        other_shape: &Shape,
+        other_data: &ShapeData,
+    ) -> Option<CollisionData> {
+        if self.shape_definitely_outside(data, other_shape, other_data) {
+            return None;
+        }
+
+        let Some(other_core) = Core::of(other_shape, other_data) else {
+            // let the ellipse collide with our outline, which is as close as
+            // `Core` says. Doing this requires us to flip the collision
+            // direction
+            return Circle
+                .collides_with_shape(other_data, &Shape::RoundedBox(*self), data)
+                .map(|collision_data| CollisionData {
+                    direction: -collision_data.direction,
+                    ..collision_data
+                });
+        };
+
+        collide_cores(&self.core(data), &other_core)
+    }
+}
+
+impl From<RoundedBox> for Mesh {
+    fn from(value: RoundedBox) -> Self {
+        value.get_mesh()
+    }
+}
+
+/// Remake the meshes of rounded shapes when their size changes, so that the
+/// corners stay round
+pub fn update_rounded_meshes(
+    query: Query<(&Shape, &Size, &Mesh2d), Changed<Size>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (shape, size, mesh) in &query {
+        let rounded_box = match shape {
+            Shape::Capsule => RoundedBox::CAPSULE,
+            Shape::RoundedBox(rounded_box) => *rounded_box,
+            _ => continue,
+        };
+        if let Some(mesh) = meshes.get_mut(&mesh.0) {
+            *mesh = rounded_box.get_sized_mesh(DVec2::from(*size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert_close;
+    use crate::shapes::SpringShape;
+
+    use super::*;
+
+    fn data_at(x: f64, y: f64, size: DVec2) -> ShapeData {
+        ShapeData {
+            position: DVec2::new(x, y),
+            rotation: 0.0,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_moment_of_inertia() {
+        let mass = 2.0;
+        let size = DVec2::new(3.0, 1.0);
+
+        // square corners are just a rectangle
+        let square = RoundedBox { corner_radius: 0.0 };
+        assert_close!(
+            square.get_moment_of_inertia(mass, size),
+            mass * (size.x * size.x + size.y * size.y) / 12.0,
+            1e-9
+        );
+
+        // a capsule as wide as it is tall is a circle
+        let radius = 0.5;
+        assert_close!(
+            RoundedBox::CAPSULE.get_moment_of_inertia(mass, DVec2::splat(2.0 * radius)),
+            mass * radius * radius / 2.0,
+            1e-9
+        );
+    }
+
+    #[test]
+    fn test_collides_with_point() {
+        let data = data_at(0.0, 0.0, DVec2::new(3.0, 1.0));
+        assert!(RoundedBox::CAPSULE.collides_with_point(&data, DVec2::new(1.4, 0.0)));
+        assert!(RoundedBox::CAPSULE.collides_with_point(&data, DVec2::new(-1.0, 0.45)));
+        // inside the corner of the bounding box, but outside the round end
+        assert!(!RoundedBox::CAPSULE.collides_with_point(&data, DVec2::new(1.45, 0.45)));
+    }
+
+    #[test]
+    fn test_collides_with_spring_as_square() {
+        // few coils, so the outline of the coils is far from convex
+        let spring = Shape::Spring(SpringShape {
+            coil_count: 2,
+            coil_diameter: 0.1,
+        });
+        let rope = data_at(0.0, 0.0, DVec2::new(2.0, 1.0));
+        // standing on the spring, between two coils
+        let capsule = data_at(0.0, 0.8, DVec2::new(0.4, 1.0));
+
+        let collision = Shape::Capsule
+            .collides_with_shape(&capsule, &spring, &rope)
+            .expect("the capsule overlaps the spring");
+        assert_close!(collision.depth, 0.2, 1e-5);
+        assert_close!(collision.direction.y, 1.0, 1e-5);
+        let collision = spring
+            .collides_with_shape(&rope, &Shape::Capsule, &capsule)
+            .expect("the capsule overlaps the spring");
+        assert_close!(collision.depth, 0.2, 1e-5);
+        assert_close!(collision.direction.y, -1.0, 1e-5);
+    }
+
+    #[test]
+    fn test_capsule_collisions() {
+        let capsule = data_at(0.0, 0.0, DVec2::new(3.0, 1.0));
+
+        // a circle just above the end of the flat side
+        let circle = data_at(1.0, 0.9, DVec2::ONE);
+        let collision = Shape::Capsule
+            .collides_with_shape(&capsule, &Shape::Circle, &circle)
+            .expect("the circle overlaps the capsule");
+        assert_close!(collision.depth, 0.1, 1e-5);
+        assert_close!(collision.direction.y, -1.0, 1e-5);
+        let collision = Shape::Circle
+            .collides_with_shape(&circle, &Shape::Capsule, &capsule)
+            .expect("the circle overlaps the capsule");
+        assert_close!(collision.direction.y, 1.0, 1e-5);
+
+        // past the round end, where the bounding boxes overlap
+        let circle = data_at(2.0, 0.8, DVec2::ONE);
+        assert!(
+            Shape::Capsule
+                .collides_with_shape(&capsule, &Shape::Circle, &circle)
+                .is_none()
+        );
+
+        // crossing capsules, where the cores themselves overlap. One has to
+        // move past the end of the other
+        let crossing = ShapeData {
+            rotation: PI / 2.0,
+            ..capsule
+        };
+        let collision = Shape::Capsule
+            .collides_with_shape(&capsule, &Shape::Capsule, &crossing)
+            .expect("the capsules cross");
+        assert_close!(collision.depth, 2.0, 1e-5);
+    }
+
+    #[test]
+    fn test_ellipse_collisions() {
+        let capsule = data_at(0.0, 0.0, DVec2::new(2.0, 0.8));
+        let radius = 0.4;
+        let semi_axes = DVec2::new(0.5, 0.2);
+
+        // the depth is the smallest overlap of the supports along any axis
+        let exact_depth = |ellipse: &ShapeData| {
+            (0..20_000)
+                .map(|i| {
+                    let axis = DVec2::from_angle(PI * f64::from(i) / 10_000.0);
+                    let core_support = 0.6 * axis.x.abs() + radius;
+                    let ellipse_support = (semi_axes * axis).length();
+                    core_support + ellipse_support - (ellipse.position).dot(axis).abs()
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+
+        for position in [
+            // on the flat side and past the round end, where the outline is exact
+            DVec2::new(0.3, 0.55),
+            DVec2::new(1.45, 0.0),
+            // against the round end, between the vertices of the outline
+            DVec2::new(1.1, 0.45),
+            DVec2::new(0.9, -0.5),
+            DVec2::new(0.8, -0.5),
+            DVec2::new(1.2, -0.3),
+            DVec2::new(1.3, 0.2),
+        ] {
+            let ellipse = data_at(position.x, position.y, 2.0 * semi_axes);
+            let collision = Shape::Capsule
+                .collides_with_shape(&capsule, &Shape::Circle, &ellipse)
+                .expect("the ellipse overlaps the capsule");
+            let exact = exact_depth(&ellipse);
+            assert!(exact > 0.0);
+            assert!(
+                (f64::from(collision.depth) - exact).abs() < 0.01 * radius,
+                "{} vs {exact} at {position}",
+                collision.depth
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_side_gets_two_contact_points() {
+        let rounded_box = Shape::RoundedBox(RoundedBox { corner_radius: 0.5 });
+        let data = data_at(0.0, 0.95, DVec2::new(2.0, 1.0));
+        let floor = data_at(0.0, 0.0, DVec2::ONE);
+
+        let collision = rounded_box
+            .collides_with_shape(&data, &Shape::Square, &floor)
+            .expect("the box rests on the floor");
+        assert_close!(collision.depth, 0.05, 1e-4);
+        assert_close!(collision.direction.y, 1.0, 1e-5);
+        assert_eq!(collision.contact_points.as_slice().len(), 2);
+    }
+}
@@ -3,12 +3,13 @@ use crate::utils::BoundingBox;
 
 use bevy::math::DVec2;
 use bevy::render::mesh::{Indices, Mesh};
+use serde::{Deserialize, Serialize};
 
 use std::f32::consts::PI;
 
 use super::CollisionData;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Spring {
     pub coil_count: u32,
     pub coil_diameter: f32,
@@ -133,6 +134,19 @@ impl ShapeImpl for Spring {
         Shape::Square.get_bounding_box(data)
     }
 
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        // a spring is square-like, so we can use the moment of inertia of a square
+        Shape::Square.get_moment_of_inertia(mass, size)
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        Shape::Square.get_area(size)
+    }
+
+    fn get_centroid(&self, _size: DVec2) -> DVec2 {
+        DVec2::ZERO
+    }
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
         // this is roughly correct
         Shape::Square.collides_with_point(data, point)
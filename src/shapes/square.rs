@@ -32,6 +32,18 @@ impl ShapeImpl for Square {
         BoundingBox::from_center_size(data.position, DVec2::new(bb_width, bb_height))
     }
 
+    fn get_moment_of_inertia(&self, mass: f64, size: DVec2) -> f64 {
+        mass * size.length_squared() / 12.0
+    }
+
+    fn get_area(&self, size: DVec2) -> f64 {
+        size.x * size.y
+    }
+
+    fn get_centroid(&self, _size: DVec2) -> DVec2 {
+        DVec2::ZERO
+    }
+
     fn collides_with_point(&self, data: &ShapeData, point: DVec2) -> bool {
         if self.point_definitely_outside(data, point) {
             return false;
@@ -50,7 +62,14 @@ impl ShapeImpl for Square {
         other_data: &ShapeData,
     ) -> Option<CollisionData> {
         if matches!(other_shape, Shape::Circle) {
-            return other_shape.collides_with_shape(other_data, &Shape::Square, data);
+            // let the circle handle the collision. Doing this requires us
+            // to flip the collision direction
+            return other_shape
+                .collides_with_shape(other_data, &Shape::Square, data)
+                .map(|collision_data| CollisionData {
+                    direction: -collision_data.direction,
+                    ..collision_data
+                });
         }
 
         if self.shape_definitely_outside(data, other_shape, other_data) {
@@ -0,0 +1,718 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{error, fmt, fs, io};
+
+use bevy::input::common_conditions::input_just_pressed;
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::{
+    BodyKind, CollisionLayers, Connection, Density, IgnoreCollisions, Joint, JointKind,
+    PhysicsMaterial, PhysicsObject, Position, Rotation, Size, Spring, SpringForce, Tangible,
+};
+use crate::physics::{
+    AdaptiveStepSize, AirResistance, Attractor, BoundaryWall, DissipatedEnergy, Drag, Gravity,
+    GravityScale, InitialForces, Integrators, MutualGravity, SubSteps, WorldBoundary,
+};
+use crate::replay::{InputQueue, SimulationInput};
+use crate::scenes::{GameScene, despawn_scene, reset_environment};
+use crate::shapes::{Compound, Shape};
+use crate::spawners::Spawner;
+
+/// Where snapshots are saved to, and whether one should be saved when the app exits
+#[derive(Resource)]
+pub struct SnapshotFile {
+    pub path: PathBuf,
+    pub save_on_exit: bool,
+}
+
+/// The snapshot that is spawned when entering `GameScene::Snapshot`
+#[derive(Resource)]
+pub struct LoadedSnapshot(pub Snapshot);
+
+#[derive(Component, Clone)]
+struct SnapshotEntity;
+
+type SnapshotQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Position,
+        Option<&'static Rotation>,
+        Option<&'static Size>,
+        (Option<&'static Shape>, Option<&'static Compound>),
+        (Option<&'static PhysicsObject>, Option<&'static Density>),
+        Option<&'static GravityScale>,
+        Option<&'static Attractor>,
+        Option<&'static Drag>,
+        (Has<Tangible>, Option<&'static CollisionLayers>),
+        Option<&'static PhysicsMaterial>,
+        Option<(
+            &'static Connection,
+            Option<&'static SpringForce>,
+            Option<&'static Joint>,
+            Has<IgnoreCollisions>,
+        )>,
+        Option<&'static MeshMaterial2d<ColorMaterial>>,
+        Option<&'static Transform>,
+    ),
+    // the walls are spawned from the boundary
+    Without<BoundaryWall>,
+>;
+
+/// Restores scenes from snapshots, and saves a snapshot on exit if requested
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        // the accelerations are restored along with everything else
+        app.configure_sets(
+            PostStartup,
+            InitialForces.run_if(not(resource_exists::<LoadedSnapshot>)),
+        )
+        .add_systems(OnEnter(GameScene::Snapshot), snapshot_setup)
+        .add_systems(
+            OnExit(GameScene::Snapshot),
+            (despawn_scene::<SnapshotEntity>, reset_environment),
+        )
+        .add_systems(
+            Last,
+            take_snapshot
+                .pipe(save_snapshot)
+                .run_if(on_event::<AppExit>)
+                .run_if(|file: Res<SnapshotFile>| file.save_on_exit),
+        );
+    }
+}
+
+/// Save a snapshot with F5, and load the saved snapshot with F9
+pub struct SnapshotKeysPlugin;
+
+impl Plugin for SnapshotKeysPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                take_snapshot
+                    .pipe(save_snapshot)
+                    .run_if(input_just_pressed(KeyCode::F5)),
+                load_snapshot.run_if(input_just_pressed(KeyCode::F9)),
+            ),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Deserialize(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access snapshot file: {err}"),
+            Self::Deserialize(err) => write!(f, "invalid snapshot file: {err}"),
+            Self::Serialize(err) => write!(f, "failed to serialize snapshot: {err}"),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {}
+
+/// The full state of the simulation. Entities refer to each other by their
+/// `id`, which is remapped to new entities when the snapshot is restored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub gravity: [f64; 2],
+    #[serde(default)]
+    pub mutual_gravity: Option<MutualGravity>,
+    #[serde(default)]
+    pub air_resistance: AirResistance,
+    #[serde(default)]
+    pub dissipated_energy: DissipatedEnergy,
+    pub integrator: Integrators,
+    /// Only used by adaptive integrators
+    #[serde(default)]
+    pub adaptive_step_size: f64,
+    /// The fixed time step. Older snapshots without one keep the current step.
+    #[serde(default)]
+    pub timestep: Option<Duration>,
+    #[serde(default)]
+    pub sub_steps: SubSteps,
+    #[serde(default)]
+    pub boundary: WorldBoundary,
+    pub entities: Vec<EntitySnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub id: u32,
+    pub position: [f64; 2],
+    pub rotation: Option<f64>,
+    pub size: Option<[f64; 2]>,
+    pub shape: Option<Shape>,
+    #[serde(default)]
+    pub compound: Option<Compound>,
+    pub physics_object: Option<PhysicsObjectSnapshot>,
+    /// Bodies with a density get their mass from their shape
+    #[serde(default)]
+    pub density: Option<f64>,
+    #[serde(default)]
+    pub gravity_scale: Option<f64>,
+    #[serde(default)]
+    pub attractor: Option<Attractor>,
+    #[serde(default)]
+    pub drag: Option<Drag>,
+    pub tangible: bool,
+    #[serde(default)]
+    pub collision_layers: Option<CollisionLayers>,
+    pub material: Option<PhysicsMaterial>,
+    pub spring: Option<SpringSnapshot>,
+    #[serde(default)]
+    pub joint: Option<JointSnapshot>,
+    /// Whether the entities connected by the spring or joint collide with
+    /// each other
+    #[serde(default)]
+    pub ignore_collisions: bool,
+    /// Linear sRGBA color
+    pub color: Option<[f32; 4]>,
+    pub z_value: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsObjectSnapshot {
+    pub velocity: [f64; 2],
+    pub acceleration: [f64; 2],
+    pub mass: f64,
+    pub angular_velocity: f64,
+    pub angular_acceleration: f64,
+    pub moment_of_inertia: f64,
+    #[serde(default)]
+    pub kind: BodyKind,
+}
+
+impl From<&PhysicsObject> for PhysicsObjectSnapshot {
+    fn from(value: &PhysicsObject) -> Self {
+        Self {
+            velocity: value.velocity.to_array(),
+            acceleration: value.acceleration.to_array(),
+            mass: value.mass,
+            angular_velocity: value.angular_velocity,
+            angular_acceleration: value.angular_acceleration,
+            moment_of_inertia: value.moment_of_inertia,
+            kind: value.kind,
+        }
+    }
+}
+
+impl From<PhysicsObjectSnapshot> for PhysicsObject {
+    fn from(value: PhysicsObjectSnapshot) -> Self {
+        Self {
+            velocity: DVec2::from(value.velocity),
+            acceleration: DVec2::from(value.acceleration),
+            mass: value.mass,
+            angular_velocity: value.angular_velocity,
+            angular_acceleration: value.angular_acceleration,
+            moment_of_inertia: value.moment_of_inertia,
+            kind: value.kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpringSnapshot {
+    pub force: SpringForce,
+    /// `id` of the first connected entity
+    pub entity1: u32,
+    /// `id` of the second connected entity
+    pub entity2: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct JointSnapshot {
+    pub kind: JointKind,
+    pub anchor1: [f64; 2],
+    pub anchor2: [f64; 2],
+    pub reference_angle: f64,
+    /// `id` of the first connected entity
+    pub entity1: u32,
+    /// `id` of the second connected entity
+    pub entity2: u32,
+}
+
+impl Snapshot {
+    #[allow(clippy::too_many_arguments)]
+    fn capture(
+        gravity: &Gravity,
+        mutual_gravity: Option<MutualGravity>,
+        air_resistance: AirResistance,
+        dissipated_energy: DissipatedEnergy,
+        integrator: Integrators,
+        adaptive_step_size: AdaptiveStepSize,
+        timestep: Duration,
+        sub_steps: SubSteps,
+        boundary: WorldBoundary,
+        materials: &Assets<ColorMaterial>,
+        query: &SnapshotQuery,
+    ) -> Self {
+        let mut entities: Vec<_> = query
+            .iter()
+            .map(
+                |(
+                    entity,
+                    position,
+                    rotation,
+                    size,
+                    (shape, compound),
+                    (physics_object, density),
+                    gravity_scale,
+                    attractor,
+                    drag,
+                    (tangible, collision_layers),
+                    material,
+                    connection,
+                    color,
+                    transform,
+                )| EntitySnapshot {
+                    id: entity.index(),
+                    position: position.0.to_array(),
+                    rotation: rotation.map(|rotation| rotation.0),
+                    size: size.map(|size| [size.width, size.height]),
+                    shape: shape.copied(),
+                    compound: compound.cloned(),
+                    physics_object: physics_object.map(PhysicsObjectSnapshot::from),
+                    density: density.map(|density| density.0),
+                    gravity_scale: gravity_scale.map(|scale| scale.0),
+                    attractor: attractor.copied(),
+                    drag: drag.copied(),
+                    tangible,
+                    collision_layers: collision_layers.copied(),
+                    material: material.copied(),
+                    spring: connection.and_then(|(connection, force, ..)| {
+                        force.map(|force| SpringSnapshot {
+                            force: *force,
+                            entity1: connection.entity1.index(),
+                            entity2: connection.entity2.index(),
+                        })
+                    }),
+                    joint: connection.and_then(|(connection, _, joint, _)| {
+                        joint.map(|joint| JointSnapshot {
+                            kind: joint.kind,
+                            anchor1: joint.anchor1.to_array(),
+                            anchor2: joint.anchor2.to_array(),
+                            reference_angle: joint.reference_angle,
+                            entity1: connection.entity1.index(),
+                            entity2: connection.entity2.index(),
+                        })
+                    }),
+                    ignore_collisions: connection
+                        .is_some_and(|(.., ignore_collisions)| ignore_collisions),
+                    color: color
+                        .and_then(|handle| materials.get(handle))
+                        .map(|material| material.color.to_linear().to_f32_array()),
+                    z_value: transform.map_or(0.0, |transform| transform.translation.z),
+                },
+            )
+            .collect();
+        entities.sort_unstable_by_key(|entity| entity.id);
+
+        Self {
+            gravity: gravity.0.to_array(),
+            mutual_gravity,
+            air_resistance,
+            dissipated_energy,
+            integrator,
+            adaptive_step_size: adaptive_step_size.0,
+            timestep: Some(timestep),
+            sub_steps,
+            boundary,
+            entities,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let contents = fs::read_to_string(path).map_err(SnapshotError::Io)?;
+        ron::from_str(&contents).map_err(SnapshotError::Deserialize)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(SnapshotError::Serialize)?;
+        fs::write(path, contents).map_err(SnapshotError::Io)
+    }
+
+    /// Spawn every entity in the snapshot tagged with `marker`, and set the
+    /// gravity, integrator and time step.
+    pub fn spawn<B: Bundle + Clone>(
+        &self,
+        marker: B,
+        commands: &mut Commands,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+    ) {
+        commands.insert_resource(Gravity(DVec2::from(self.gravity)));
+        match self.mutual_gravity {
+            Some(mutual_gravity) => commands.insert_resource(mutual_gravity),
+            None => commands.remove_resource::<MutualGravity>(),
+        }
+        commands.insert_resource(self.air_resistance);
+        commands.insert_resource(self.dissipated_energy);
+        commands.insert_resource(self.integrator);
+        commands.insert_resource(AdaptiveStepSize(self.adaptive_step_size));
+        commands.insert_resource(self.sub_steps);
+        commands.insert_resource(self.boundary);
+        if let Some(timestep) = self.timestep {
+            commands.queue(move |world: &mut World| {
+                world.resource_mut::<Time<Fixed>>().set_timestep(timestep);
+            });
+        }
+
+        let mut entities = HashMap::with_capacity(self.entities.len());
+        for snapshot in &self.entities {
+            let mut spawner = Spawner::new(marker.clone(), commands)
+                .with_bundle(Position(DVec2::from(snapshot.position)));
+            if let Some(rotation) = snapshot.rotation {
+                spawner = spawner.with_bundle(Rotation(rotation));
+            }
+            if let Some([width, height]) = snapshot.size {
+                spawner = spawner.with_bundle(Size { width, height });
+            }
+            if let Some(physics_object) = snapshot.physics_object {
+                spawner = spawner.with_bundle(PhysicsObject::from(physics_object));
+            }
+            if let Some(density) = snapshot.density {
+                spawner = spawner.with_bundle(Density(density));
+            }
+            if let Some(scale) = snapshot.gravity_scale {
+                spawner = spawner.with_bundle(GravityScale(scale));
+            }
+            if let Some(attractor) = snapshot.attractor {
+                spawner = spawner.with_bundle(attractor);
+            }
+            if let Some(drag) = snapshot.drag {
+                spawner = spawner.with_bundle(drag);
+            }
+            if snapshot.tangible {
+                spawner = spawner.with_bundle(Tangible);
+            }
+            if let Some(layers) = snapshot.collision_layers {
+                spawner = spawner.with_bundle(layers);
+            }
+            if let Some(material) = snapshot.material {
+                spawner = spawner.with_bundle(material);
+            }
+            if let Some(shape) = snapshot.shape {
+                spawner = spawner
+                    .with_shape(shape, meshes)
+                    .with_z_value(snapshot.z_value);
+            }
+            if let Some(compound) = &snapshot.compound {
+                spawner = spawner
+                    .with_compound(compound.clone(), meshes)
+                    .with_z_value(snapshot.z_value);
+            }
+            if let Some(color) = snapshot.color {
+                spawner = spawner.with_color(
+                    Color::LinearRgba(LinearRgba::from_f32_array(color)),
+                    materials,
+                );
+            }
+            entities.insert(snapshot.id, spawner.id());
+        }
+
+        // connections can only be remapped once every entity exists
+        let remap = |id| entities.get(&id).copied().unwrap_or(Entity::PLACEHOLDER);
+        for snapshot in &self.entities {
+            if let Some(spring) = snapshot.spring {
+                commands.entity(entities[&snapshot.id]).insert((
+                    Spring,
+                    spring.force,
+                    Connection {
+                        entity1: remap(spring.entity1),
+                        entity2: remap(spring.entity2),
+                    },
+                ));
+            }
+            if let Some(joint) = snapshot.joint {
+                commands.entity(entities[&snapshot.id]).insert((
+                    Joint {
+                        kind: joint.kind,
+                        anchor1: DVec2::from(joint.anchor1),
+                        anchor2: DVec2::from(joint.anchor2),
+                        reference_angle: joint.reference_angle,
+                    },
+                    Connection {
+                        entity1: remap(joint.entity1),
+                        entity2: remap(joint.entity2),
+                    },
+                ));
+            }
+            if snapshot.ignore_collisions {
+                commands
+                    .entity(entities[&snapshot.id])
+                    .insert(IgnoreCollisions);
+            }
+        }
+    }
+}
+
+fn snapshot_setup(
+    snapshot: Res<LoadedSnapshot>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    debug!("Setting up scene from snapshot");
+
+    snapshot
+        .0
+        .spawn(SnapshotEntity, &mut commands, &mut meshes, &mut materials);
+}
+
+/// Capture the current state of the simulation
+#[allow(clippy::too_many_arguments)]
+pub fn take_snapshot(
+    gravity: Res<Gravity>,
+    mutual_gravity: Option<Res<MutualGravity>>,
+    air_resistance: Res<AirResistance>,
+    dissipated_energy: Res<DissipatedEnergy>,
+    integrator: Res<Integrators>,
+    adaptive_step_size: Res<AdaptiveStepSize>,
+    fixed_time: Res<Time<Fixed>>,
+    sub_steps: Res<SubSteps>,
+    boundary: Res<WorldBoundary>,
+    materials: Res<Assets<ColorMaterial>>,
+    query: SnapshotQuery,
+) -> Snapshot {
+    Snapshot::capture(
+        &gravity,
+        mutual_gravity.as_deref().copied(),
+        *air_resistance,
+        *dissipated_energy,
+        *integrator,
+        *adaptive_step_size,
+        fixed_time.timestep(),
+        *sub_steps,
+        *boundary,
+        &materials,
+        &query,
+    )
+}
+
+fn save_snapshot(In(snapshot): In<Snapshot>, snapshot_file: Res<SnapshotFile>) {
+    match snapshot.save(&snapshot_file.path) {
+        Ok(()) => info!("Saved snapshot to {}", snapshot_file.path.display()),
+        Err(err) => error!("{err}"),
+    }
+}
+
+/// Loading a snapshot changes the simulation, so it goes through the input queue
+fn load_snapshot(snapshot_file: Res<SnapshotFile>, mut queue: ResMut<InputQueue>) {
+    match Snapshot::load(&snapshot_file.path) {
+        Ok(snapshot) => queue.push(SimulationInput::LoadSnapshot(snapshot)),
+        Err(err) => error!("{err}"),
+    }
+}
+
+#[cfg(test)]
+impl Snapshot {
+    /// The same snapshot, with the entities ordered and numbered by what they
+    /// contain. The `Entity` ids differ between worlds even when the entities
+    /// are the same.
+    pub(crate) fn without_ids(&self) -> Self {
+        let contents = |entity: &EntitySnapshot| {
+            let mut entity = entity.clone();
+            entity.id = 0;
+            if let Some(spring) = &mut entity.spring {
+                (spring.entity1, spring.entity2) = (0, 0);
+            }
+            if let Some(joint) = &mut entity.joint {
+                (joint.entity1, joint.entity2) = (0, 0);
+            }
+            format!("{entity:?}")
+        };
+
+        let mut snapshot = self.clone();
+        snapshot.entities.sort_by_cached_key(contents);
+        let ids: HashMap<_, _> = snapshot
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(i, entity)| (entity.id, i as u32))
+            .collect();
+        for entity in &mut snapshot.entities {
+            entity.id = ids[&entity.id];
+            if let Some(spring) = &mut entity.spring {
+                spring.entity1 = ids[&spring.entity1];
+                spring.entity2 = ids[&spring.entity2];
+            }
+            if let Some(joint) = &mut entity.joint {
+                joint.entity1 = ids[&joint.entity1];
+                joint.entity2 = ids[&joint.entity2];
+            }
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::components::SpringLaw;
+    use crate::physics::BoundaryKind;
+
+    fn capture(world: &mut World) -> Snapshot {
+        world.run_system_once(take_snapshot).unwrap()
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<ColorMaterial>>();
+        world.init_resource::<Integrators>();
+        world.init_resource::<Gravity>();
+        world.init_resource::<AdaptiveStepSize>();
+        world.init_resource::<Time<Fixed>>();
+        world.init_resource::<SubSteps>();
+        world.init_resource::<AirResistance>();
+        world.init_resource::<DissipatedEnergy>();
+        world.init_resource::<WorldBoundary>();
+        world
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut world = new_world();
+        world.insert_resource(Gravity(DVec2::new(0.1, -0.2 - 0.1)));
+        world.insert_resource(Integrators::DormandPrince);
+        world.insert_resource(MutualGravity {
+            constant: 0.5,
+            softening: 0.1,
+            theta: 0.0,
+        });
+        world.insert_resource(AdaptiveStepSize(0.0123));
+        world.insert_resource(Time::<Fixed>::from_hz(144.0));
+        world.insert_resource(SubSteps(4));
+        world.insert_resource(WorldBoundary {
+            kind: BoundaryKind::Wrap,
+            half_size: [5.0, 2.5],
+        });
+        world.insert_resource(AirResistance(Drag {
+            linear: 0.0,
+            quadratic: 0.25,
+            scale_with_width: true,
+        }));
+
+        let anchor = world.spawn(Position(DVec2::new(1.0 / 3.0, 2.0))).id();
+        // spawn and despawn an entity so the ids don't start at zero
+        let unused = world.spawn_empty().id();
+        world.despawn(unused);
+        let body = world
+            .spawn((
+                Shape::Circle,
+                Position(DVec2::new(0.1 + 0.2, -1e-300)),
+                Rotation(std::f64::consts::PI),
+                PhysicsObject {
+                    velocity: DVec2::new(1.0 / 7.0, 3.0),
+                    acceleration: DVec2::new(-9.81, 1e-17),
+                    angular_velocity: 0.7,
+                    kind: BodyKind::Kinematic,
+                    ..PhysicsObject::at_rest(0.3)
+                },
+                GravityScale(-0.5),
+                Density(0.25),
+                Drag {
+                    linear: 0.1,
+                    quadratic: 0.0,
+                    scale_with_width: false,
+                },
+                Tangible,
+                CollisionLayers {
+                    memberships: 0b10,
+                    filter: 0b01,
+                },
+            ))
+            .id();
+        world.spawn((
+            ron::from_str::<Compound>(
+                "[
+                    (shape: Capsule, size: (1.0, 0.2), mass: 0.4),
+                    (shape: Triangle, offset: (0.5, 0.3), rotation: 0.1, size: (0.3, 0.3), mass: 0.2),
+                ]",
+            )
+            .unwrap(),
+            Position(DVec2::new(2.0, 0.0)),
+            Size {
+                width: 2.0,
+                height: 1.0,
+            },
+            PhysicsObject::at_rest(0.6),
+            Tangible,
+        ));
+        world.spawn((
+            Position(DVec2::new(-3.0, 0.5)),
+            Attractor {
+                strength: 20.0,
+                softening: 0.0,
+            },
+        ));
+        world.spawn((
+            Spring,
+            Position::default(),
+            SpringForce {
+                damping: 0.01,
+                spring_constant: 12.5,
+                equilibrium_length: 2f64.sqrt(),
+                law: SpringLaw::Cubic { coefficient: -0.3 },
+                max_strain: Some(0.5),
+            },
+            Connection {
+                entity1: anchor,
+                entity2: body,
+            },
+        ));
+        world.spawn((
+            Position::default(),
+            Joint {
+                kind: JointKind::Prismatic { axis: 0.25 },
+                anchor1: DVec2::new(0.0, -0.5),
+                anchor2: DVec2::new(0.1, 0.0),
+                reference_angle: -1.5,
+            },
+            Connection {
+                entity1: body,
+                entity2: anchor,
+            },
+            IgnoreCollisions,
+        ));
+
+        let snapshot = capture(&mut world);
+        let serialized =
+            ron::ser::to_string_pretty(&snapshot, ron::ser::PrettyConfig::default()).unwrap();
+        let deserialized: Snapshot = ron::from_str(&serialized).unwrap();
+        assert_eq!(snapshot, deserialized);
+
+        let mut restored_world = new_world();
+        restored_world
+            .run_system_once(
+                move |mut commands: Commands,
+                      mut meshes: ResMut<Assets<Mesh>>,
+                      mut materials: ResMut<Assets<ColorMaterial>>| {
+                    deserialized.spawn(SnapshotEntity, &mut commands, &mut meshes, &mut materials);
+                },
+            )
+            .unwrap();
+        let restored = capture(&mut restored_world);
+
+        // the entity ids differ, but everything else should be identical
+        assert_eq!(snapshot.without_ids(), restored.without_ids());
+        assert_eq!(restored.integrator, Integrators::DormandPrince);
+    }
+}
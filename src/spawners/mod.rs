@@ -1,9 +1,10 @@
 pub mod spring;
 pub mod square;
 
-use crate::shapes::{Shape, ShapeImpl};
+use crate::shapes::{Compound, Shape, ShapeImpl};
 
 use bevy::asset::AssetPath;
+use bevy::math::DVec2;
 use bevy::prelude::*;
 
 pub struct Spawner<'a, 'w, 's> {
@@ -28,6 +29,14 @@ impl<'a, 'w, 's> Spawner<'a, 'w, 's> {
         spawner
     }
 
+    /// Make the entity a compound of several shapes. The mesh is remade for
+    /// the actual size of the entity once it is shown.
+    pub fn with_compound(self, compound: Compound, meshes: &mut ResMut<Assets<Mesh>>) -> Self {
+        let spawner = self.with_mesh(compound.get_sized_mesh(DVec2::ONE), meshes);
+        spawner.commands.entity(spawner.entity).insert(compound);
+        spawner
+    }
+
     pub fn with_mesh(self, mesh: impl Into<Mesh>, meshes: &mut ResMut<Assets<Mesh>>) -> Self {
         self.commands
             .entity(self.entity)
@@ -1,4 +1,4 @@
-use crate::components::{Connection, Size, Spring, SpringForce};
+use crate::components::{Connection, Size, Spring, SpringForce, SpringLaw};
 
 use bevy::prelude::*;
 
@@ -20,6 +20,8 @@ pub const fn spring_bundle(
             damping,
             spring_constant,
             equilibrium_length,
+            law: SpringLaw::Linear,
+            max_strain: None,
         },
         Connection { entity1, entity2 },
     )
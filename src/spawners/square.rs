@@ -1,17 +1,19 @@
-use crate::components::{PhysicsObject, Position, Size, Tangible};
+use crate::components::{Density, PhysicsObject, Position, Size, Tangible};
 
 use bevy::math::DVec2;
 
-pub const fn physics_square_bundle(
-    mass: f64,
+/// A tangible square whose mass follows its size
+pub const fn dense_square_bundle(
+    density: f64,
     width: f64,
     height: f64,
     position: DVec2,
-) -> (Position, Size, PhysicsObject, Tangible) {
+) -> (Position, Size, PhysicsObject, Density, Tangible) {
     (
         Position(position),
         Size { width, height },
-        PhysicsObject::at_rest(mass),
+        PhysicsObject::at_rest(density * width * height),
+        Density(density),
         Tangible,
     )
 }
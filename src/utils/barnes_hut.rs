@@ -0,0 +1,197 @@
+use bevy::math::DVec2;
+
+use crate::utils::BoundingBox;
+
+/// Bodies closer together than the smallest cell at this depth share a leaf
+const MAX_DEPTH: u32 = 32;
+
+enum Contents {
+    /// Indices of the bodies in the leaf
+    Leaf(Vec<usize>),
+    /// Indices of the non-empty child nodes
+    Branch(Vec<usize>),
+}
+
+struct Node {
+    bounds: BoundingBox,
+    mass: f64,
+    center_of_mass: DVec2,
+    contents: Contents,
+}
+
+/// A quadtree of point masses for approximating their mutual gravitation.
+///
+/// Every node stores the total mass and center of mass of the bodies inside
+/// it. A node that is small compared to its distance from a body is treated
+/// as a single point mass, which brings the cost of finding the pull on every
+/// body down from O(n²) to O(n log n).
+pub struct BarnesHutTree {
+    /// Position and mass of every body
+    bodies: Vec<(DVec2, f64)>,
+    nodes: Vec<Node>,
+}
+
+impl BarnesHutTree {
+    pub fn new(bodies: Vec<(DVec2, f64)>) -> Self {
+        let mut tree = Self {
+            bodies,
+            nodes: Vec::new(),
+        };
+        if tree.bodies.is_empty() {
+            return tree;
+        }
+
+        let (min, max) = tree.bodies.iter().fold(
+            (DVec2::INFINITY, DVec2::NEG_INFINITY),
+            |(min, max), (position, _)| (min.min(*position), max.max(*position)),
+        );
+        // square cells keep the opening criterion the same in both directions
+        let side = (max - min).max_element().max(f64::EPSILON);
+        let bounds = BoundingBox::from_center_size((min + max) * 0.5, DVec2::splat(side));
+
+        tree.build((0..tree.bodies.len()).collect(), bounds, 0);
+        tree
+    }
+
+    /// Add a node for `indices` and everything below it, returning its index
+    fn build(&mut self, indices: Vec<usize>, bounds: BoundingBox, depth: u32) -> usize {
+        let (mass, weighted_position) =
+            indices
+                .iter()
+                .fold((0.0, DVec2::ZERO), |(mass, weighted_position), &index| {
+                    let (position, body_mass) = self.bodies[index];
+                    (mass + body_mass, weighted_position + body_mass * position)
+                });
+        let center_of_mass = if mass == 0.0 {
+            bounds.center()
+        } else {
+            weighted_position / mass
+        };
+
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: bounds.clone(),
+            mass,
+            center_of_mass,
+            contents: Contents::Leaf(Vec::new()),
+        });
+
+        if indices.len() <= 1 || depth >= MAX_DEPTH {
+            self.nodes[node].contents = Contents::Leaf(indices);
+            return node;
+        }
+
+        let center = bounds.center();
+        let mut quadrants: [Vec<usize>; 4] = Default::default();
+        for index in indices {
+            let position = self.bodies[index].0;
+            let quadrant =
+                usize::from(position.x >= center.x) + 2 * usize::from(position.y >= center.y);
+            quadrants[quadrant].push(index);
+        }
+
+        let mut children = Vec::with_capacity(4);
+        for (quadrant, indices) in quadrants.into_iter().enumerate() {
+            if indices.is_empty() {
+                continue;
+            }
+            let corner = DVec2::new(
+                if quadrant % 2 == 0 {
+                    bounds.min.x
+                } else {
+                    bounds.max.x
+                },
+                if quadrant < 2 {
+                    bounds.min.y
+                } else {
+                    bounds.max.y
+                },
+            );
+            let child_bounds = BoundingBox::from_corners(center, corner);
+            children.push(self.build(indices, child_bounds, depth + 1));
+        }
+        self.nodes[node].contents = Contents::Branch(children);
+        node
+    }
+
+    /// Call `f` with the offset to and mass of everything pulling on body
+    /// `index`. Nodes whose size divided by their distance is less than
+    /// `theta` are passed as a single point mass, so a `theta` of zero visits
+    /// every other body.
+    pub fn visit(&self, index: usize, theta: f64, mut f: impl FnMut(DVec2, f64)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let position = self.bodies[index].0;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            match &node.contents {
+                Contents::Leaf(bodies) => {
+                    for &other in bodies {
+                        if other != index {
+                            let (other_position, mass) = self.bodies[other];
+                            f(other_position - position, mass);
+                        }
+                    }
+                }
+                Contents::Branch(children) => {
+                    let offset = node.center_of_mass - position;
+                    if !node.bounds.contains(position)
+                        && node.bounds.width() < theta * offset.length()
+                    {
+                        f(offset, node.mass);
+                    } else {
+                        stack.extend(children);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The softened pull on a body from a unit gravitational constant, and
+    /// the sum of the magnitudes of the pulls that make it up
+    fn field(tree: &BarnesHutTree, index: usize, theta: f64) -> (DVec2, f64) {
+        let mut field = DVec2::ZERO;
+        let mut magnitude = 0.0;
+        tree.visit(index, theta, |offset, mass| {
+            let distance_squared = offset.length_squared() + 0.01;
+            field += mass * offset / distance_squared.powf(1.5);
+            magnitude += mass / distance_squared;
+        });
+        (field, magnitude)
+    }
+
+    #[test]
+    fn test_barnes_hut() {
+        // a deterministic scattering of bodies, with two in the same place
+        let mut bodies: Vec<_> = (0..300)
+            .map(|i| {
+                let t = f64::from(i);
+                let position = DVec2::new((t * 7.3).sin() * 10.0, (t * 3.1).cos() * 4.0);
+                (position, 1.0 + (t * 1.7).sin().abs())
+            })
+            .collect();
+        bodies.push((bodies[0].0 + DVec2::X, 2.0));
+        bodies.push((bodies[0].0 + DVec2::X, 3.0));
+        let tree = BarnesHutTree::new(bodies.clone());
+
+        for index in 0..bodies.len() {
+            let mut visited = 0;
+            tree.visit(index, 0.0, |_, _| visited += 1);
+            assert_eq!(visited, bodies.len() - 1);
+
+            // the pulls can nearly cancel out, so the error is compared to
+            // their total size instead of the size of the field
+            let (exact, magnitude) = field(&tree, index, 0.0);
+            let (approximate, _) = field(&tree, index, 0.5);
+            assert!((approximate - exact).length() < 0.02 * magnitude);
+        }
+    }
+}
@@ -54,4 +54,9 @@ impl BoundingBox {
 
         x_intersects && y_intersects
     }
+
+    #[inline]
+    pub fn contains(&self, point: DVec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
 }
@@ -1,14 +1,18 @@
+mod barnes_hut;
 mod bounding_box;
 mod macros;
 mod newton_solver;
 mod quadratic_solver;
 mod shape_projection;
+mod sweep_and_prune;
 mod vector_conversion;
 mod wrapping_windows;
 
+pub use barnes_hut::BarnesHutTree;
 pub use bounding_box::BoundingBox;
 pub use newton_solver::global_newton_solver;
 pub use quadratic_solver::solve_quadratic;
 pub use shape_projection::{DEdge, Edge, ShapeProjection};
+pub use sweep_and_prune::sweep_and_prune;
 pub use vector_conversion::{ToVec, ToVector};
 pub use wrapping_windows::WrappingWindows;
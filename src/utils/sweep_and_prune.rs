@@ -0,0 +1,86 @@
+use bevy::math::DVec2;
+
+use crate::utils::BoundingBox;
+
+/// Find all pairs of intersecting bounding boxes, with each unordered pair
+/// returned only once.
+///
+/// The boxes are sorted along the axis where they are most spread out. We
+/// then sweep along that axis while keeping a list of the boxes we are
+/// currently inside of, so only boxes that overlap along the sweep axis
+/// are tested against each other.
+pub fn sweep_and_prune<T: Copy>(items: &[(T, BoundingBox)]) -> Vec<(T, T)> {
+    if items.len() < 2 {
+        return Vec::new();
+    }
+
+    let count = items.len() as f64;
+    let mean = items.iter().map(|(_, b)| b.center()).sum::<DVec2>() / count;
+    let variance = items
+        .iter()
+        .map(|(_, b)| {
+            let deviation = b.center() - mean;
+            deviation * deviation
+        })
+        .sum::<DVec2>();
+    let axis = if variance.x >= variance.y {
+        DVec2::X
+    } else {
+        DVec2::Y
+    };
+
+    let mut sorted: Vec<_> = items.iter().collect();
+    sorted.sort_by(|(_, a), (_, b)| a.min.dot(axis).total_cmp(&b.min.dot(axis)));
+
+    let mut pairs = Vec::new();
+    let mut active: Vec<&(T, BoundingBox)> = Vec::new();
+    for item @ (id, bounding_box) in sorted {
+        let start = bounding_box.min.dot(axis);
+        active.retain(|(_, other)| other.max.dot(axis) > start);
+
+        for (other_id, other_box) in &active {
+            if bounding_box.intersects(other_box) {
+                pairs.push((*other_id, *id));
+            }
+        }
+        active.push(item);
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_and_prune() {
+        // a deterministic scattering of boxes of different sizes
+        let items: Vec<_> = (0..200)
+            .map(|i| {
+                let t = f64::from(i);
+                let center = DVec2::new((t * 7.3).sin() * 10.0, (t * 3.1).cos() * 4.0);
+                let size = DVec2::new(0.5 + (t * 1.7).sin().abs(), 0.5 + (t * 2.3).cos().abs());
+                (i, BoundingBox::from_center_size(center, size))
+            })
+            .collect();
+
+        let mut expected = Vec::new();
+        for (i, (id1, box1)) in items.iter().enumerate() {
+            for (id2, box2) in &items[i + 1..] {
+                if box1.intersects(box2) {
+                    expected.push((*id1, *id2));
+                }
+            }
+        }
+
+        let mut got: Vec<_> = sweep_and_prune(&items)
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        got.sort_unstable();
+
+        assert!(!expected.is_empty());
+        assert_eq!(got, expected);
+    }
+}